@@ -0,0 +1,115 @@
+use std::io::Write;
+
+use clap::Command;
+use clap_complete::Shell;
+
+/// project names for `--project`, read from the log file that would be used
+const PROJECTS: &str =
+    "record-hours list 2>/dev/null | sed -e 's/: .*//' -e 's/^(default)$/default/'";
+
+/// write a completion script for `shell` to `f`, completing `--project` with the recorded
+/// projects where the shell allows adding to the generated script
+pub fn generate(shell: Shell, command: &mut Command, mut f: impl Write) -> anyhow::Result<()> {
+    let name = command.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, command, &name, &mut script);
+    let script = String::from_utf8(script)?;
+
+    match shell {
+        Shell::Bash => {
+            // wraps the generated function, which completes everything but the project names
+            let generated = format!("_{name}");
+            let function = format!("_{}_projects", name.replace('-', "_"));
+            writeln!(f, "{script}")?;
+            writeln!(f, "{function}() {{")?;
+            writeln!(f, "    case \"${{COMP_WORDS[COMP_CWORD-1]}}\" in")?;
+            writeln!(
+                f,
+                "        -p|--project) COMPREPLY=($(compgen -W \"$({PROJECTS})\" -- \"$2\")) ;;"
+            )?;
+            writeln!(f, "        *) {generated} \"$@\" ;;")?;
+            writeln!(f, "    esac")?;
+            writeln!(f, "}}")?;
+            writeln!(f, "complete -F {function} -o bashdefault -o default {name}")?;
+        }
+        Shell::Zsh => {
+            // the action the generated script completes the value of --project with differs
+            // between versions of clap_complete, it is replaced by a function defined after
+            // the `#compdef` line
+            let function = format!("_{}_projects", name.replace('-', "_"));
+            let script = replace_action(&script, ":PROJECT:", &function);
+            let (compdef, script) = script.split_once('\n').unwrap_or(("", &script));
+            writeln!(f, "{compdef}")?;
+            writeln!(f, "{function}() {{")?;
+            writeln!(
+                f,
+                "    local projects; projects=(${{(f)\"$({PROJECTS})\"}})"
+            )?;
+            writeln!(f, "    compadd -a projects")?;
+            writeln!(f, "}}")?;
+            write!(f, "{script}")?;
+        }
+        Shell::Fish => {
+            write!(f, "{script}")?;
+            writeln!(
+                f,
+                "complete -c {name} -s p -l project -x -a '({})'",
+                PROJECTS.replace('\'', "\\'")
+            )?;
+        }
+        _ => write!(f, "{script}")?,
+    }
+    Ok(())
+}
+
+/// replace the action up to the closing quote after `value` in every zsh argument spec
+fn replace_action(script: &str, value: &str, action: &str) -> String {
+    let mut replaced = String::with_capacity(script.len());
+    let mut rest = script;
+    while let Some(at) = rest.find(value) {
+        let (before, after) = rest.split_at(at + value.len());
+        replaced.push_str(before);
+        replaced.push_str(action);
+        rest = &after[after.find('\'').unwrap_or(0)..];
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+    use crate::App;
+
+    fn script(shell: Shell) -> String {
+        let mut out = Vec::new();
+        generate(shell, &mut App::command(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn projects_are_completed() {
+        let bash = script(Shell::Bash);
+        assert!(bash.contains("complete -F _record_hours_projects"));
+        assert!(bash.contains("--dry-run"));
+
+        let zsh = script(Shell::Zsh);
+        assert!(zsh.starts_with("#compdef record-hours\n"));
+        assert!(zsh.contains(":PROJECT:_record_hours_projects'"));
+        assert!(!zsh.contains(":PROJECT: '"));
+        assert!(!zsh.contains(":PROJECT:_default'"));
+
+        assert_eq!(
+            replace_action(
+                "'-p+[]:PROJECT: ' \\\n'--project=[]:PROJECT:_default' \\\n",
+                ":PROJECT:",
+                "_projects"
+            ),
+            "'-p+[]:PROJECT:_projects' \\\n'--project=[]:PROJECT:_projects' \\\n"
+        );
+
+        assert!(script(Shell::Fish).contains("-l project -x -a '(record-hours list"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::{collections::BTreeMap, fs::File, path::PathBuf};
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+
+use chrono::Duration;
+
+use crate::{format::DurationFormatter, parse_duration, project_key};
+
+/// defaults read from `record-hours.json` in the working directory or
+/// `$XDG_CONFIG_HOME/record-hours/config.json`.
+///
+/// Command line flags override these, which in turn override the built-in defaults
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// project used when `--project` is not given
+    pub default_project: Option<String>,
+    /// merge tolerance for new timestamps, in seconds
+    pub default_tolerance_seconds: Option<u32>,
+    /// template used by `show` when neither `--format`, `--json` nor `--markdown` is given
+    pub default_format: Option<String>,
+    /// daily target of `show` and `summary` when `--target` is not given, e.g. "6h"
+    pub default_target: Option<String>,
+    /// how `show` and `totals` print durations unless --decimal is given, e.g. "%H:%M" for 1:30.
+    /// See `DurationFormatter` for the tokens
+    pub duration_format: Option<String>,
+    /// log file used when neither `--file` nor `$RECORD_HOURS_FILE` is given
+    pub log_file: Option<PathBuf>,
+    /// log files that can be selected with `--profile <name>`, a leading `~/` is the home
+    /// directory
+    pub profiles: BTreeMap<String, PathBuf>,
+    /// how many previous versions of the log to keep as `<log>.1`, `<log>.2` and so on
+    pub backup_count: usize,
+}
+
+impl Config {
+    fn candidates() -> Vec<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+        let mut candidates = vec![PathBuf::from("record-hours.json")];
+        candidates.extend(config_home.map(|dir| dir.join("record-hours").join("config.json")));
+        candidates
+    }
+
+    /// read the first config file that exists, or the defaults if there is none
+    pub fn load() -> anyhow::Result<Self> {
+        let Some(path) = Self::candidates().into_iter().find(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+        log::info!("reading config from {}", path.display());
+
+        let file = File::open(&path)?;
+        serde_json::from_reader(file)
+            .with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// the log file of the profile `name`
+    pub fn profile(&self, name: &str) -> anyhow::Result<PathBuf> {
+        let path = self
+            .profiles
+            .get(name)
+            .ok_or(anyhow!("profile {name} is not defined in the config"))?;
+        match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+            (Ok(rest), Some(home)) => Ok(PathBuf::from(home).join(rest)),
+            _ => Ok(path.clone()),
+        }
+    }
+
+    /// the target given on the command line, falling back to the configured default
+    pub fn target(&self, target: Option<Duration>) -> anyhow::Result<Option<Duration>> {
+        match (target, &self.default_target) {
+            (Some(target), _) => Ok(Some(target)),
+            (None, Some(default)) => parse_duration(default)
+                .map(Some)
+                .with_context(|| format!("invalid default_target {default:?}")),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// the configured duration format, checked for unknown tokens
+    pub fn duration_format(&self) -> anyhow::Result<Option<&str>> {
+        match &self.duration_format {
+            Some(format) => {
+                DurationFormatter::check(format)
+                    .context("invalid duration_format in the config")?;
+                Ok(Some(format))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// the key of the project given on the command line, falling back to the project of the
+    /// closest marker file and then to the configured default
+    pub fn project(&self, project: Option<String>) -> String {
+        project_key(
+            project
+                .or_else(marker_project)
+                .or_else(|| self.default_project.clone()),
+        )
+    }
+}
+
+/// file whose content is the project to use in its directory and all directories below
+const PROJECT_MARKER: &str = ".record-hours-project";
+
+/// the project of the first marker file in the working directory or any of its parents
+fn marker_project() -> Option<String> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors().find_map(|dir| {
+        let path = dir.join(PROJECT_MARKER);
+        let project = std::fs::read_to_string(&path).ok()?;
+        let project = project.trim();
+        if project.is_empty() {
+            return None;
+        }
+        log::info!("using project {project} from {}", path.display());
+        Some(project.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::{App, Commands};
+
+    #[test]
+    fn target_from_config() {
+        let config = Config {
+            default_target: Some("7h30m".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.target(None).unwrap(), Some(Duration::minutes(450)));
+        assert_eq!(
+            config.target(Some(Duration::hours(6))).unwrap(),
+            Some(Duration::hours(6))
+        );
+        assert_eq!(Config::default().target(None).unwrap(), None);
+    }
+
+    #[test]
+    fn target_too_long() {
+        let config = Config {
+            default_target: Some("99999999999999999999h".to_string()),
+            ..Default::default()
+        };
+        assert!(config.target(None).is_err());
+
+        assert!(
+            App::try_parse_from(["record-hours", "show", "--target", "99999999999999999999h"])
+                .is_err()
+        );
+        let app = App::try_parse_from(["record-hours", "show", "--target", "6h"]).unwrap();
+        assert!(matches!(app.command, Commands::Show(_)));
+    }
+}
@@ -0,0 +1,214 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::{Duration, NaiveDateTime};
+use clap::ValueEnum;
+use record_hours::{Date, DecimalDuration, Item, Time};
+use serde::Serialize;
+
+use crate::display_project;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    /// iCalendar, one event per interval
+    Ics,
+    /// JSON Lines, one object per interval
+    Jsonl,
+}
+
+/// a line of the jsonl export
+#[derive(Serialize)]
+struct JsonlInterval<'a> {
+    project: &'a str,
+    date: Date,
+    start: Time,
+    end: Time,
+    seconds: i64,
+}
+
+impl ExportFormat {
+    pub fn header(&self, mut f: impl Write) -> anyhow::Result<()> {
+        match self {
+            ExportFormat::Csv => writeln!(f, "project,date,start,end,duration_hours")?,
+            ExportFormat::Ics => write!(
+                f,
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//record-hours//EN\r\n"
+            )?,
+            ExportFormat::Jsonl => {}
+        }
+        Ok(())
+    }
+
+    pub fn footer(&self, mut f: impl Write) -> anyhow::Result<()> {
+        match self {
+            ExportFormat::Csv | ExportFormat::Jsonl => {}
+            ExportFormat::Ics => write!(f, "END:VCALENDAR\r\n")?,
+        }
+        Ok(())
+    }
+
+    pub fn day(
+        &self,
+        mut f: impl Write,
+        project: &str,
+        date: &Date,
+        times: &[Item],
+        last: Option<Time>,
+    ) -> anyhow::Result<()> {
+        if let Some(start) = last {
+            eprintln!(
+                "warning: skipping open interval starting {date} {start} in {}",
+                display_project(project)
+            );
+        }
+
+        match self {
+            ExportFormat::Csv => {
+                for item in times {
+                    let duration = DecimalDuration::new(item.duration());
+                    writeln!(
+                        f,
+                        "{},{date},{},{},{duration}",
+                        csv_field(project),
+                        item.start,
+                        item.end
+                    )?;
+                }
+            }
+            ExportFormat::Ics => {
+                let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ");
+                for item in times {
+                    let start = NaiveDateTime::new(date.0, item.start.0);
+                    let end = start + item.duration();
+                    // derived from the interval's start only, so exporting again yields the
+                    // same uid even if the interval has been extended since
+                    let uid = format!(
+                        "{}-{:016x}@record-hours",
+                        start.format("%Y%m%dT%H%M%S"),
+                        fnv1a(project)
+                    );
+                    write!(
+                        f,
+                        "BEGIN:VEVENT\r\nUID:{uid}\r\nDTSTAMP:{stamp}\r\nDTSTART:{}\r\nDTEND:{}\r\nSUMMARY:{}\r\nEND:VEVENT\r\n",
+                        start.format("%Y%m%dT%H%M%S"),
+                        end.format("%Y%m%dT%H%M%S"),
+                        ics_text(display_project(project)),
+                    )?;
+                }
+            }
+            ExportFormat::Jsonl => {
+                for item in times {
+                    let line = JsonlInterval {
+                        project,
+                        date: *date,
+                        start: item.start,
+                        end: item.end,
+                        seconds: item.duration().num_seconds(),
+                    };
+                    serde_json::to_writer(&mut f, &line)?;
+                    writeln!(f)?;
+                }
+                // every day is written out right away, so a consumer can start reading early
+                f.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// write csv with a column of decimal hours per project and a row per week of `weeks`, each
+/// with the durations of `projects` in that order
+pub fn matrix(
+    mut f: impl Write,
+    projects: &[&str],
+    weeks: &BTreeMap<String, Vec<Duration>>,
+) -> anyhow::Result<()> {
+    let hours = |duration: Duration| match duration.is_zero() {
+        true => "0".to_string(),
+        false => DecimalDuration::new(duration).to_string(),
+    };
+    write!(f, "week")?;
+    for project in projects {
+        write!(f, ",{}", csv_field(display_project(project)))?;
+    }
+    writeln!(f, ",total")?;
+    for (week, durations) in weeks {
+        write!(f, "{week}")?;
+        for &duration in durations {
+            write!(f, ",{}", hours(duration))?;
+        }
+        writeln!(f, ",{}", hours(durations.iter().sum()))?;
+    }
+    Ok(())
+}
+
+/// escape a value of an ics text property
+fn ics_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' | ';' | ',' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// small stable hash, the std hashers are not guaranteed to be stable across releases
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// quote a field if it contains characters that are special to csv
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+
+    use super::*;
+
+    #[test]
+    fn ics_begin_end_pairs() {
+        let date = Date(chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        let item = |start, end| Item {
+            start: Time(NaiveTime::from_hms_opt(start, 0, 0).unwrap()),
+            end: Time(NaiveTime::from_hms_opt(end, 0, 0).unwrap()),
+            note: None,
+            correction: Duration::zero(),
+        };
+        let mut out = Vec::new();
+        ExportFormat::Ics.header(&mut out).unwrap();
+        ExportFormat::Ics
+            .day(&mut out, "work", &date, &[item(9, 12), item(13, 17)], None)
+            .unwrap();
+        ExportFormat::Ics.footer(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let mut open = Vec::new();
+        for line in out.split_terminator("\r\n") {
+            if let Some(name) = line.strip_prefix("BEGIN:") {
+                open.push(name);
+            } else if let Some(name) = line.strip_prefix("END:") {
+                assert_eq!(open.pop(), Some(name), "unbalanced END:{name}");
+            }
+        }
+        assert!(open.is_empty(), "unclosed {open:?}");
+        assert!(out.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(out.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(out.matches("BEGIN:VEVENT").count(), 2);
+    }
+}
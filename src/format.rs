@@ -1,14 +1,17 @@
 use std::fmt::Display;
 
+use anyhow::anyhow;
 use chrono::Duration;
+use record_hours::{Date, DecimalDuration};
 
-use crate::{Date, DecimalDuration};
+use crate::display_project;
 
 pub struct Formatter<'a> {
     pub date: Date,
     pub duration: Duration,
     pub format: &'a str,
     pub project: &'a str,
+    pub note: &'a str,
 }
 
 impl Display for Formatter<'_> {
@@ -27,10 +30,63 @@ impl Display for Formatter<'_> {
                 Some('Y') => write!(f, "{}", self.date.0.format("%Y"))?,
                 Some('M') => write!(f, "{}", self.date.0.format("%m"))?,
                 Some('D') => write!(f, "{}", self.date.0.format("%d"))?,
-                Some('t') => write!(f, "{}", DecimalDuration(self.duration))?,
+                Some('w') => write!(f, "{}", self.date.0.format("%a"))?,
+                Some('W') => write!(f, "{}", self.date.0.format("%V"))?,
+                Some('t') => write!(f, "{}", DecimalDuration::new(self.duration))?,
                 Some('h') => write!(f, "{}", self.duration.num_hours())?,
                 Some('m') => write!(f, "{}", self.duration.num_minutes())?,
-                Some('P') => write!(f, "{}", self.project)?, //the project
+                Some('P') => write!(f, "{}", display_project(self.project))?, //the project
+                Some('n') => write!(f, "{}", self.note)?,
+                _ => return Err(std::fmt::Error),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// renders a duration according to `format`, where %H are the whole hours, %M the remaining
+/// minutes as two digits, %m all minutes and %h the decimal hours, e.g. "%H:%M" gives 1:30.
+///
+/// Negative durations get a single leading `-`
+pub struct DurationFormatter<'a> {
+    pub duration: Duration,
+    pub format: &'a str,
+}
+
+impl DurationFormatter<'_> {
+    /// fail if `format` contains an unknown token
+    pub fn check(format: &str) -> anyhow::Result<()> {
+        let fmt = DurationFormatter {
+            duration: Duration::zero(),
+            format,
+        };
+        let mut out = String::new();
+        std::fmt::write(&mut out, format_args!("{fmt}"))
+            .map_err(|_| anyhow!("invalid duration format {format:?}, use %H, %M, %m, %h or %%"))
+    }
+}
+
+impl Display for DurationFormatter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let duration = self.duration.abs();
+        if self.duration.num_minutes() < 0 {
+            write!(f, "-")?;
+        }
+        let mut chars = self.format.chars();
+
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                write!(f, "{ch}")?;
+                continue;
+            }
+
+            match chars.next() {
+                Some('%') => write!(f, "%")?,
+                Some('H') => write!(f, "{}", duration.num_hours())?,
+                Some('M') => write!(f, "{:02}", duration.num_minutes() % 60)?,
+                Some('m') => write!(f, "{}", duration.num_minutes())?,
+                Some('h') => write!(f, "{}", DecimalDuration::new(duration))?,
                 _ => return Err(std::fmt::Error),
             }
         }
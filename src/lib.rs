@@ -0,0 +1,1832 @@
+//! recording and reading worked hours.
+//!
+//! A [`Log`] maps project names to their [`Project`], which holds the [`TimeStamp`]s of every
+//! day. [`Record`] modifies a log at the time of a [`Clock`], [`intervals_for_day`] pairs up the
+//! timestamps of a day into [`Item`]s, and [`MyDuration`], [`SecondsDuration`] and
+//! [`DecimalDuration`] format durations. Everything else is part of the command line tool and
+//! may change at any time
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context};
+use chrono::{
+    DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{
+    de::{self, MapAccess, Unexpected, Visitor},
+    ser::SerializeMap,
+    Deserialize, Serialize,
+};
+
+/// a calendar day, written as YYYY-MM-DD
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[serde(transparent)]
+pub struct Date(pub NaiveDate);
+
+impl FromStr for Date {
+    type Err = chrono::ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let inner = NaiveDate::parse_from_str(s, "%Y-%m-%d")?;
+        Ok(Date(inner))
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)?;
+        Ok(())
+    }
+}
+
+/// a time of day, written as HH:MM
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[serde(transparent)]
+pub struct Time(pub NaiveTime);
+
+impl FromStr for Time {
+    type Err = anyhow::Error;
+    /// accepts HH:MM, HH:MM:SS and 12 hour times like 9:00am
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ["%H:%M", "%H:%M:%S", "%I:%M%p", "%I:%M %p"]
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(s.trim(), format).ok())
+            .map(Time)
+            .ok_or(anyhow!(
+                "invalid time {s:?}, use HH:MM, HH:MM:SS or a 12 hour time like 9:00am"
+            ))
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.format("%H:%M"))?;
+        Ok(())
+    }
+}
+
+/// the whole log file, every project by name. The default project has an empty name
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(transparent)]
+pub struct Log {
+    pub projects: BTreeMap<String, Project>,
+}
+
+/// the timestamps of a project, by day, whether its time is billable and its events.
+///
+/// Projects without a classification or events are written as just the map of days, like
+/// before there were either, others as `{"billable": true, "events": [...], "entries": {...}}`
+#[derive(Debug, Default)]
+pub struct Project {
+    pub entries: BTreeMap<Date, Vec<TimeStamp>>,
+    pub billable: Option<bool>,
+    /// things that happened at a point in time, sorted by date and time
+    pub events: Vec<Event>,
+}
+
+impl Serialize for Project {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.billable.is_none() && self.events.is_empty() {
+            return self.entries.serialize(serializer);
+        }
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(billable) = self.billable {
+            map.serialize_entry("billable", &billable)?;
+        }
+        if !self.events.is_empty() {
+            map.serialize_entry("events", &self.events)?;
+        }
+        map.serialize_entry("entries", &self.entries)?;
+        map.end()
+    }
+}
+
+/// something that happened at a point in time, like a release, without a duration
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub date: Date,
+    pub time: Time,
+    pub label: String,
+}
+
+impl<'de> Deserialize<'de> for Project {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ProjectVisitor)
+    }
+}
+
+/// reads both ways a `Project` is written, the keys tell them apart as they are never dates
+struct ProjectVisitor;
+
+impl<'de> Visitor<'de> for ProjectVisitor {
+    type Value = Project;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a map of days to timestamps")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Project, A::Error> {
+        let mut project = Project::default();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "billable" => project.billable = Some(map.next_value()?),
+                "events" => project.events = map.next_value()?,
+                "entries" => project
+                    .entries
+                    .extend(map.next_value::<BTreeMap<Date, Vec<TimeStamp>>>()?),
+                day => {
+                    let date = day.parse().map_err(|_| {
+                        de::Error::invalid_value(Unexpected::Str(day), &"a date like 2024-01-31")
+                    })?;
+                    project.entries.insert(date, map.next_value()?);
+                }
+            }
+        }
+        Ok(project)
+    }
+}
+
+impl Project {
+    /// sum of all completed intervals over all days
+    pub fn total(&self) -> Duration {
+        self.entries
+            .values()
+            .map(|day| {
+                let (items, _) = intervals_for_day(day);
+                items.iter().map(Item::duration).sum::<Duration>()
+            })
+            .sum()
+    }
+
+    /// add all entries of `other`, keeping days that appear in both sorted by time.
+    ///
+    /// Identical timestamps and events are only kept once, and the classification of `other` is only taken
+    /// if this project has none. Returns how many days and timestamps were added
+    pub fn merge(&mut self, other: Project) -> (usize, usize) {
+        self.billable = self.billable.or(other.billable);
+        for event in other.events {
+            if !self.events.contains(&event) {
+                self.events.push(event);
+            }
+        }
+        self.events.sort_by_key(|event| (event.date, event.time));
+        let mut added = (0, 0);
+        for (date, timestamps) in other.entries {
+            if !self.entries.contains_key(&date) {
+                added.0 += 1;
+            }
+            let day = self.entries.entry(date).or_default();
+            // counted before sorting, which also drops repeated timestamps that were already there
+            for timestamp in timestamps {
+                if !day.contains(&timestamp) {
+                    added.1 += 1;
+                }
+                day.push(timestamp);
+            }
+            sort_day(day);
+        }
+        added
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Entry {
+    pub date: Date,
+    pub timestamps: Vec<TimeStamp>,
+}
+
+/// the first bytes of every gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// default merge tolerance in seconds
+pub const DEFAULT_TOLERANCE: u32 = 60 * 15;
+
+/// a single clock in or clock out
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct TimeStamp {
+    #[serde(rename = "type")]
+    pub typ: TimeStampType,
+    pub time: Time,
+    /// tolerance (for how to merge entries) in seconds
+    pub tolerance: u32,
+    /// what was worked on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// offset from UTC in seconds that `time` was recorded in. Timestamps without one were
+    /// recorded in the local timezone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utc_offset: Option<i32>,
+    /// when the timestamp was recorded, breaks ties between timestamps with the same time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recorded_at: Option<NaiveDateTime>,
+}
+
+/// the timestamps of a day as the parts that sorting keeps together: every start with the end
+/// that follows it, and any other timestamp on its own
+fn intervals(day: Vec<TimeStamp>) -> Vec<Vec<TimeStamp>> {
+    let mut intervals: Vec<Vec<TimeStamp>> = vec![];
+    for timestamp in day {
+        match intervals.last_mut() {
+            Some(interval)
+                if interval.len() == 1 && interval[0].is_start() && timestamp.is_end() =>
+            {
+                interval.push(timestamp)
+            }
+            _ => intervals.push(vec![timestamp]),
+        }
+    }
+    intervals
+}
+
+/// sort the intervals of a day by their start, equal times in the order they were recorded, and
+/// drop repeated ones. An end stays behind its start, so an interval ending after midnight, with
+/// an end before its start, is kept intact
+fn sort_day(day: &mut Vec<TimeStamp>) {
+    let mut sorted = intervals(std::mem::take(day));
+    sorted.sort_by_key(|interval| (interval[0].time, interval[0].recorded_at));
+    sorted.dedup();
+    day.extend(sorted.into_iter().flatten());
+    day.dedup();
+}
+
+impl TimeStamp {
+    pub fn is_start(&self) -> bool {
+        self.typ == TimeStampType::Start
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.typ == TimeStampType::End
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TimeStampType {
+    Start,
+    End,
+}
+
+/// what `Record::insert` did
+#[derive(Debug, Clone, Copy)]
+pub enum Insertion {
+    /// a new interval was started
+    Started,
+    /// the open interval that began at `start` was closed
+    Stopped { start: Time },
+    /// the end of the interval that began at `start` was moved from `previous` to now, because
+    /// it was still within the tolerance
+    Extended { start: Option<Time>, previous: Time },
+}
+
+/// where `Record` gets the current date and time from
+pub trait Clock {
+    fn now(&self) -> DateTime<FixedOffset>;
+}
+
+/// the local time of the system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalClock;
+
+impl Clock for LocalClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        chrono::offset::Local::now().fixed_offset()
+    }
+}
+
+/// a clock that is stuck at the given time
+impl Clock for DateTime<FixedOffset> {
+    fn now(&self) -> DateTime<FixedOffset> {
+        *self
+    }
+}
+
+/// a log that is being modified at a given date and time
+pub struct Record {
+    pub log: Log,
+    pub date: Date,
+    pub time: Time,
+    /// the offset from UTC that `time` is in
+    pub offset: FixedOffset,
+    /// the actual time of the clock, even if `date` or `time` are changed
+    pub recorded_at: NaiveDateTime,
+    /// the tolerance new timestamps are recorded with unless another one is given, in seconds
+    pub tolerance: u32,
+}
+impl Log {
+    /// the project with the most recent timestamp, and whether its last interval is still open
+    pub fn last_project(&self) -> Option<(&str, bool)> {
+        self.projects
+            .iter()
+            .filter_map(|(name, project)| {
+                let (date, day) = project
+                    .entries
+                    .iter()
+                    .rev()
+                    .find(|(_, day)| !day.is_empty())?;
+                let last = day.last()?;
+                Some(((*date, last.time), name.as_str(), last.is_start()))
+            })
+            .max_by_key(|(when, _, _)| *when)
+            .map(|(_, name, open)| (name, open))
+    }
+
+    pub fn read(mut input: impl Read, format: Format) -> anyhow::Result<Self> {
+        let mut buf = Vec::new();
+        input.read_to_end(&mut buf)?;
+
+        let buf = decompress(buf)?;
+
+        let log: Log = if buf.is_empty() {
+            log::warn!("file was empty, using default");
+            Log::default()
+        } else {
+            format.parse(&buf)?
+        };
+
+        log::info!("read {log:#?}");
+        Ok(log)
+    }
+
+    /// convert every timestamp that was recorded in another offset than `tz` had at that moment,
+    /// for showing the log.
+    ///
+    /// An interval whose start is converted past midnight moves to the day it then starts on, its
+    /// end stays with it even if only the end crosses midnight
+    pub fn localize(&mut self, tz: &impl TimeZone) {
+        for project in self.projects.values_mut() {
+            let mut changed = BTreeSet::new();
+            for (date, day) in std::mem::take(&mut project.entries) {
+                let day = if day.is_empty() {
+                    vec![vec![]]
+                } else {
+                    intervals(day)
+                };
+                for mut interval in day {
+                    let mut target = date;
+                    for (i, timestamp) in interval.iter_mut().enumerate() {
+                        let Some(offset) = timestamp.utc_offset else {
+                            continue;
+                        };
+                        let Some(utc) = NaiveDateTime::new(date.0, timestamp.time.0)
+                            .checked_sub_signed(Duration::seconds(offset as i64))
+                        else {
+                            continue;
+                        };
+                        let local = tz.from_utc_datetime(&utc);
+                        let local_offset = local.offset().fix().local_minus_utc();
+                        if local_offset == offset {
+                            continue;
+                        }
+                        timestamp.time = Time(local.time());
+                        timestamp.utc_offset = Some(local_offset);
+                        if i == 0 {
+                            target = Date(local.date_naive());
+                        }
+                        changed.insert(target);
+                    }
+                    project.entries.entry(target).or_default().extend(interval);
+                }
+            }
+            for date in changed {
+                if let Some(day) = project.entries.get_mut(&date) {
+                    sort_day(day);
+                }
+            }
+        }
+    }
+}
+
+impl Record {
+    /// read the log from `input`, recording at the current time of `clock`
+    pub fn open(input: impl Read, format: Format, clock: &impl Clock) -> anyhow::Result<Self> {
+        let log = Log::read(input, format)?;
+
+        let now = clock.now();
+        Ok(Self {
+            log,
+            date: Date(now.date_naive()),
+            time: Time(now.time()),
+            offset: *now.offset(),
+            recorded_at: now.naive_local(),
+            tolerance: DEFAULT_TOLERANCE,
+        })
+    }
+
+    /// clock in or out of `project`, depending on whether an interval is open.
+    ///
+    /// Unless `merge` is false, a clock in within the tolerance of the previous end moves that end
+    /// instead. `tolerance` replaces the one the previous end was recorded with and is recorded
+    /// with the new timestamp.
+    ///
+    /// A single timestamp before the last one of the day leaves two starts or two ends next to
+    /// each other, so that fails unless `force` is given
+    pub fn insert(
+        &mut self,
+        project: String,
+        tolerance: Option<u32>,
+        note: Option<String>,
+        merge: bool,
+        force: bool,
+    ) -> anyhow::Result<Insertion> {
+        // an interval started late yesterday is still open shortly after midnight
+        if let Some((date, start)) = self
+            .open_start(&project)
+            .filter(|&(date, _)| date != self.date)
+        {
+            self.put(project, date, TimeStampType::End, tolerance, note);
+            return Ok(Insertion::Stopped { start });
+        }
+
+        let project_info = self.log.projects.entry(project).or_default();
+        let now = NaiveDateTime::new(self.date.0, self.time.0);
+
+        // shortly after midnight, a stop from late yesterday is still within the tolerance. Moving
+        // it past midnight is fine, as an end before its start counts as crossing midnight and
+        // `sort_day` keeps it behind its start
+        let before_today = project_info
+            .entries
+            .get(&self.date)
+            .and_then(|day| day.first())
+            .is_none_or(|first| self.time < first.time);
+        let yesterday = self.date.0.pred_opt().map(Date);
+        if let Some(day) = yesterday
+            .filter(|_| merge && before_today)
+            .and_then(|yesterday| project_info.entries.get_mut(&yesterday))
+        {
+            let start = day.iter().rev().find(|t| t.is_start()).map(|t| t.time);
+            if let Some(last_timestamp) = day.last_mut() {
+                // the stop may already have been moved past midnight
+                let date = match start.is_none_or(|start| start <= last_timestamp.time) {
+                    true => yesterday.unwrap_or(self.date),
+                    false => self.date,
+                };
+                if should_merge(last_timestamp, date.0, now, tolerance) {
+                    let previous = last_timestamp.time;
+                    last_timestamp.time = self.time;
+                    last_timestamp.utc_offset = Some(self.offset.local_minus_utc());
+                    last_timestamp.recorded_at = Some(self.recorded_at);
+                    if note.is_some() {
+                        last_timestamp.note = note;
+                    }
+                    return Ok(Insertion::Extended { start, previous });
+                }
+            }
+        }
+
+        let entry = project_info.entries.entry(self.date).or_default();
+
+        // backfilled timestamps may be older than the ones already recorded, so everything is
+        // decided relative to the timestamp right before the new one
+        let index = entry.partition_point(|t| t.time <= self.time);
+        let session_start = |before: usize| {
+            entry[..before]
+                .iter()
+                .rev()
+                .find(|t| t.is_start())
+                .map(|t| t.time)
+        };
+
+        if let Some(i) = index.checked_sub(1).filter(|_| merge) {
+            if should_merge(&entry[i], self.date.0, now, tolerance) {
+                let start = session_start(i);
+                let last_timestamp = &mut entry[i];
+                let previous = last_timestamp.time;
+                last_timestamp.time = self.time;
+                last_timestamp.utc_offset = Some(self.offset.local_minus_utc());
+                last_timestamp.recorded_at = Some(self.recorded_at);
+                if note.is_some() {
+                    last_timestamp.note = note;
+                }
+                return Ok(Insertion::Extended { start, previous });
+            }
+        }
+
+        if let Some(next) = entry.get(index).filter(|_| !force) {
+            return Err(anyhow!(
+                "{} is before the {:?} at {} on {}, adding it there would leave two starts or two \
+                 ends in a row. Use --force to add it anyway",
+                self.time,
+                next.typ,
+                next.time,
+                self.date
+            ));
+        }
+
+        let (typ, insertion) = match index.checked_sub(1) {
+            Some(i) if entry[i].is_start() => (
+                TimeStampType::End,
+                Insertion::Stopped {
+                    start: entry[i].time,
+                },
+            ),
+            _ => (TimeStampType::Start, Insertion::Started),
+        };
+
+        entry.insert(
+            index,
+            TimeStamp {
+                typ,
+                time: self.time,
+                tolerance: tolerance.unwrap_or(self.tolerance),
+                note,
+                utc_offset: Some(self.offset.local_minus_utc()),
+                recorded_at: Some(self.recorded_at),
+            },
+        );
+        Ok(insertion)
+    }
+
+    /// the date and start of the interval of `project` that is open at the time of the record.
+    ///
+    /// Before the first timestamp of the day, that is the last start of yesterday if it is later
+    /// than the time of the record, as an end before its start counts as crossing midnight
+    pub fn open_start(&self, project: &str) -> Option<(Date, Time)> {
+        let project = self.log.projects.get(project)?;
+        let entry = project.entries.get(&self.date).map_or(&[][..], |day| day);
+        let index = entry.partition_point(|t| t.time <= self.time);
+        if let Some(before) = index.checked_sub(1).map(|i| &entry[i]) {
+            return before.is_start().then_some((self.date, before.time));
+        }
+        let yesterday = Date(self.date.0.pred_opt()?);
+        let last = project.entries.get(&yesterday)?.last()?;
+        (last.is_start() && self.time < last.time).then_some((yesterday, last.time))
+    }
+
+    /// insert a timestamp of type `typ` into `project` on `date` at the time of the record, like
+    /// `insert` but without deciding the type from the timestamps around it
+    fn put(
+        &mut self,
+        project: String,
+        date: Date,
+        typ: TimeStampType,
+        tolerance: Option<u32>,
+        note: Option<String>,
+    ) {
+        let timestamp = TimeStamp {
+            typ,
+            time: self.time,
+            tolerance: tolerance.unwrap_or(self.tolerance),
+            note,
+            utc_offset: Some(self.offset.local_minus_utc()),
+            recorded_at: Some(self.recorded_at),
+        };
+        let entry = self
+            .log
+            .projects
+            .entry(project)
+            .or_default()
+            .entries
+            .entry(date)
+            .or_default();
+        // on an earlier day, the timestamp ends the interval open across midnight
+        let index = match date == self.date {
+            true => entry.partition_point(|t| t.time <= self.time),
+            false => entry.len(),
+        };
+        entry.insert(index, timestamp);
+    }
+
+    /// start an interval of `project`, without ever merging into or stopping another one.
+    ///
+    /// Fails if `project` is already clocked in, unless `force` is given, which closes the open
+    /// interval first and returns its start
+    pub fn clock_in(
+        &mut self,
+        project: String,
+        tolerance: Option<u32>,
+        note: Option<String>,
+        force: bool,
+    ) -> anyhow::Result<Option<Time>> {
+        let open = self.open_start(&project);
+        if let Some((date, start)) = open {
+            if !force {
+                return Err(anyhow!(
+                    "already clocked in since {start}, use --force to restart"
+                ));
+            }
+            self.put(project.clone(), date, TimeStampType::End, tolerance, None);
+        }
+        self.put(project, self.date, TimeStampType::Start, tolerance, note);
+        Ok(open.map(|(_, start)| start))
+    }
+
+    /// close the interval of `project` that is open at the time of the record, returns its start
+    pub fn clock_out(
+        &mut self,
+        project: String,
+        tolerance: Option<u32>,
+        note: Option<String>,
+    ) -> anyhow::Result<Time> {
+        let (date, start) = self
+            .open_start(&project)
+            .ok_or(anyhow!("not clocked in, there is no open interval to stop"))?;
+        self.put(project, date, TimeStampType::End, tolerance, note);
+        Ok(start)
+    }
+
+    /// close the open interval of `project`, which before anything is recorded today may be
+    /// the one started late yesterday
+    pub fn stop(&mut self, project: String) -> anyhow::Result<()> {
+        let today = self
+            .log
+            .projects
+            .get(&project)
+            .and_then(|project| project.entries.get(&self.date))
+            .and_then(|entry| entry.last());
+        let date = match today {
+            Some(last) => last.is_start().then_some(self.date),
+            None => self.open_start(&project).map(|(date, _)| date),
+        }
+        .ok_or(anyhow!("no open interval to stop"))?;
+
+        let entry = self
+            .log
+            .projects
+            .entry(project)
+            .or_default()
+            .entries
+            .entry(date)
+            .or_default();
+        entry.push(TimeStamp {
+            typ: TimeStampType::End,
+            time: self.time,
+            tolerance: self.tolerance,
+            note: None,
+            utc_offset: Some(self.offset.local_minus_utc()),
+            recorded_at: Some(self.recorded_at),
+        });
+        Ok(())
+    }
+
+    /// add an interval of `project` that lasted `duration` and ended now
+    pub fn add(
+        &mut self,
+        project: String,
+        duration: Duration,
+        tolerance: Option<u32>,
+        note: Option<String>,
+    ) -> anyhow::Result<Item> {
+        if duration <= Duration::zero() || duration > self.time.0 - NaiveTime::MIN {
+            return Err(anyhow!(
+                "an interval of {} ending at {} would not start on the same day",
+                MyDuration(duration),
+                self.time
+            ));
+        }
+        let item = Item {
+            start: Time(self.time.0 - duration),
+            end: self.time,
+            note: note.clone(),
+            correction: Duration::zero(),
+        };
+
+        let day = self
+            .log
+            .projects
+            .entry(project)
+            .or_default()
+            .entries
+            .entry(self.date)
+            .or_default();
+        let index = day.partition_point(|t| t.time < item.start);
+        let inside = index.checked_sub(1).is_some_and(|i| day[i].is_start());
+        if inside || day.get(index).is_some_and(|t| t.time <= item.end) {
+            return Err(anyhow!(
+                "{} - {} overlaps an interval that is already recorded",
+                item.start,
+                item.end
+            ));
+        }
+
+        let timestamp = |typ, time| TimeStamp {
+            typ,
+            time,
+            tolerance: tolerance.unwrap_or(self.tolerance),
+            note: note.clone(),
+            utc_offset: Some(self.offset.local_minus_utc()),
+            recorded_at: Some(self.recorded_at),
+        };
+        day.splice(
+            index..index,
+            [
+                timestamp(TimeStampType::Start, item.start),
+                timestamp(TimeStampType::End, item.end),
+            ],
+        );
+        Ok(item)
+    }
+
+    /// split the interval of `project` on `date` that contains `at` into two intervals, the first
+    /// ending and the second starting at `at`. Returns the interval that was split
+    pub fn split(&mut self, project: &str, date: Date, at: Time) -> anyhow::Result<Item> {
+        let day = self
+            .log
+            .projects
+            .get_mut(project)
+            .and_then(|project| project.entries.get_mut(&date))
+            .ok_or(anyhow!(
+                "project {} has no entries on {date}",
+                display_project(project)
+            ))?;
+
+        let i = (0..day.len().saturating_sub(1))
+            .find(|&i| {
+                let (start, end) = (&day[i], &day[i + 1]);
+                let inside = if start.time <= end.time {
+                    start.time < at && at < end.time
+                } else {
+                    // the interval crossed midnight
+                    start.time < at || at < end.time
+                };
+                start.is_start() && end.is_end() && inside
+            })
+            .ok_or(anyhow!("no interval on {date} contains {at}"))?;
+
+        let item = Item {
+            note: None,
+            ..Item::between(&day[i], &day[i + 1])
+        };
+        let timestamp = |typ| TimeStamp {
+            typ,
+            time: at,
+            tolerance: day[i + 1].tolerance,
+            note: None,
+            utc_offset: day[i + 1].utc_offset,
+            recorded_at: Some(self.recorded_at),
+        };
+        let split = [
+            timestamp(TimeStampType::End),
+            timestamp(TimeStampType::Start),
+        ];
+        day.splice(i + 1..i + 1, split);
+        Ok(item)
+    }
+
+    /// remove `project`, returning its entries
+    pub fn delete(&mut self, project: &str) -> anyhow::Result<Project> {
+        self.log.projects.remove(project).ok_or(anyhow!(
+            "project {} is not present in log file",
+            display_project(project)
+        ))
+    }
+
+    /// rename `from` to `to`, merging into `to` if that already exists
+    pub fn rename(&mut self, from: &str, to: String) -> anyhow::Result<()> {
+        let project = self.log.projects.remove(from).ok_or(anyhow!(
+            "project {} is not present in log file",
+            display_project(from)
+        ))?;
+        self.log.projects.entry(to).or_default().merge(project);
+        Ok(())
+    }
+
+    /// add an event called `label` to `project` at the time of the record
+    pub fn mark(&mut self, project: String, label: String) {
+        let events = &mut self.log.projects.entry(project).or_default().events;
+        let index =
+            events.partition_point(|event| (event.date, event.time) <= (self.date, self.time));
+        events.insert(
+            index,
+            Event {
+                date: self.date,
+                time: self.time,
+                label,
+            },
+        );
+    }
+
+    /// move the timestamps of `from` on `date` to `to`, merging them into the ones `to` already
+    /// has on that day. Returns how many timestamps were moved
+    pub fn move_day(&mut self, date: Date, from: &str, to: String) -> anyhow::Result<usize> {
+        let timestamps = self
+            .log
+            .projects
+            .get_mut(from)
+            .ok_or(anyhow!(
+                "project {} is not present in log file",
+                display_project(from)
+            ))?
+            .entries
+            .remove(&date)
+            .ok_or(anyhow!(
+                "project {} has no entries on {date}",
+                display_project(from)
+            ))?;
+        let moved = timestamps.len();
+        let day = Project {
+            entries: BTreeMap::from([(date, timestamps)]),
+            ..Project::default()
+        };
+        self.log.projects.entry(to).or_default().merge(day);
+        Ok(moved)
+    }
+
+    /// remove every start that is directly followed by an end less than `min` later.
+    ///
+    /// Only such adjacent pairs are removed, so the remaining sequence never gains a dangling
+    /// end. Returns the date and interval of everything that was removed
+    pub fn prune(&mut self, project: &str, min: Duration) -> anyhow::Result<Vec<(Date, Item)>> {
+        let project_info = self.log.projects.get_mut(project).ok_or(anyhow!(
+            "project {} is not present in log file",
+            display_project(project)
+        ))?;
+
+        let mut removed = vec![];
+        for (date, day) in project_info.entries.iter_mut() {
+            let mut i = 0;
+            while i + 1 < day.len() {
+                let item = Item::between(&day[i], &day[i + 1]);
+                if day[i].is_start() && day[i + 1].is_end() && item.duration() < min {
+                    day.drain(i..i + 2);
+                    removed.push((*date, item));
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        project_info.entries.retain(|_, day| !day.is_empty());
+        Ok(removed)
+    }
+
+    /// merge every project of `other` into the log, returns the number of added projects, days
+    /// and timestamps
+    pub fn import(&mut self, other: Log) -> (usize, usize, usize) {
+        let mut added = (0, 0, 0);
+        for (name, project) in other.projects {
+            if !self.log.projects.contains_key(&name) {
+                added.0 += 1;
+            }
+            let (days, timestamps) = self.log.projects.entry(name).or_default().merge(project);
+            added.1 += days;
+            added.2 += timestamps;
+        }
+        added
+    }
+
+    /// write the log to `output` in `format`
+    pub fn commit(&self, output: impl Write, format: Format) -> anyhow::Result<()> {
+        format.write(&self.log, output)
+    }
+
+    /// write the log to `path`.
+    ///
+    /// The log is first written to a temporary file next to `path` which then replaces it, so the
+    /// original is left untouched if writing fails halfway through. Before it is replaced, the
+    /// original is kept as `path.1`, up to `backups` older versions are kept as `path.2` and so on.
+    /// Missing parent directories are created. The log is written in the format its extension
+    /// names, see [`Format::of`], and compressed if it is named like `hours.json.gz`
+    pub fn save(&self, path: &Path, backups: usize) -> anyhow::Result<()> {
+        if is_stdin(path) {
+            return Err(anyhow!(
+                "the log was read from stdin and can't be written back, use a file instead"
+            ));
+        }
+        let format = Format::of(path);
+        let name = path
+            .file_name()
+            .ok_or(anyhow!("{} is not a file", path.display()))?;
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(name);
+        tmp_name.push(".tmp");
+        let tmp = path.with_file_name(tmp_name);
+
+        // so a log can be started in a directory that doesn't exist yet
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                anyhow!(
+                    "could not create the directory {} of the log: {e}",
+                    parent.display()
+                )
+            })?;
+        }
+
+        let result = File::create(&tmp)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut outfile| {
+                if is_gzip(path) {
+                    let mut encoder = GzEncoder::new(outfile, Compression::default());
+                    self.commit(&mut encoder, format)?;
+                    outfile = encoder.finish()?;
+                } else {
+                    self.commit(&mut outfile, format)?;
+                }
+                outfile.sync_all()?;
+                Ok(())
+            });
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e);
+        }
+
+        if backups != 0 && path.exists() {
+            let backup = |n: usize| {
+                let mut name = name.to_os_string();
+                name.push(format!(".{n}"));
+                path.with_file_name(name)
+            };
+            for n in (1..backups).rev() {
+                if backup(n).exists() {
+                    std::fs::rename(backup(n), backup(n + 1))?;
+                }
+            }
+            std::fs::copy(path, backup(1))?;
+        }
+
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// a completed interval
+#[derive(Serialize)]
+pub struct Item {
+    pub start: Time,
+    pub end: Time,
+    /// note of the start, or of the end if the start has none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// how much longer the interval lasted than its times say, e.g. -1h across the start of
+    /// daylight saving time
+    #[serde(skip)]
+    pub correction: Duration,
+}
+
+/// `end - start` as times of day, an end before its start means the interval crossed midnight
+fn time_between(start: NaiveTime, end: NaiveTime) -> Duration {
+    let delta = end - start;
+    if delta < Duration::zero() {
+        delta + Duration::days(1)
+    } else {
+        delta
+    }
+}
+
+impl Item {
+    /// the interval from `start` to `end`, with the note of either. If both know their offset from
+    /// UTC, the duration is the time that actually passed between them
+    pub fn between(start: &TimeStamp, end: &TimeStamp) -> Self {
+        let naive = time_between(start.time.0, end.time.0);
+        let correction = match (start.utc_offset, end.utc_offset) {
+            (Some(start_offset), Some(end_offset)) => {
+                let utc = |time: Time, offset: i32| time.0 - Duration::seconds(offset as i64);
+                let (start, end) = (utc(start.time, start_offset), utc(end.time, end_offset));
+                time_between(start, end) - naive
+            }
+            _ => Duration::zero(),
+        };
+        Item {
+            start: start.time,
+            end: end.time,
+            note: start.note.clone().or_else(|| end.note.clone()),
+            correction,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        let delta = time_between(self.start.0, self.end.0) + self.correction;
+        // whole seconds, like everything is displayed in
+        Duration::seconds(delta.num_seconds())
+    }
+}
+
+/// a duration as e.g. 1h45min, 2h or 45min, negative ones as e.g. -1h45min
+pub struct MyDuration(pub Duration);
+
+impl Display for MyDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let duration = self.0.abs();
+        if self.0.num_minutes() < 0 {
+            write!(f, "-")?;
+        }
+        let (hours, minutes) = (duration.num_hours(), duration.num_minutes() % 60);
+        if hours != 0 {
+            write!(f, "{hours}h")?;
+        }
+        if minutes != 0 || hours == 0 {
+            write!(f, "{minutes}min")?;
+        }
+        Ok(())
+    }
+}
+
+/// like `MyDuration`, but also shows seconds, e.g. 1h5min3s, 2h or 3s
+pub struct SecondsDuration(pub Duration);
+
+impl Display for SecondsDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let duration = self.0.abs();
+        if self.0.num_seconds() < 0 {
+            write!(f, "-")?;
+        }
+        let (hours, minutes, seconds) = (
+            duration.num_hours(),
+            duration.num_minutes() % 60,
+            duration.num_seconds() % 60,
+        );
+        if hours != 0 {
+            write!(f, "{hours}h")?;
+        }
+        if minutes != 0 {
+            write!(f, "{minutes}min")?;
+        }
+        if seconds != 0 || duration.num_minutes() == 0 {
+            write!(f, "{seconds}s")?;
+        }
+        Ok(())
+    }
+}
+
+/// a duration in hours, e.g. 1.75
+pub struct DecimalDuration {
+    duration: Duration,
+    /// number of decimal places
+    precision: usize,
+}
+
+impl DecimalDuration {
+    pub fn new(duration: Duration) -> Self {
+        Self::with_precision(duration, 2)
+    }
+
+    pub fn with_precision(duration: Duration, precision: usize) -> Self {
+        Self {
+            duration,
+            precision,
+        }
+    }
+}
+
+impl Display for DecimalDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hours = self.duration.num_seconds() as f64 / 3600.0;
+        write!(f, "{:.*}", self.precision, hours)?;
+        Ok(())
+    }
+}
+
+/// pair up the starts and ends of a single day.
+///
+/// Returns the completed intervals and the start of a trailing interval that was never closed.
+/// Ends that come before the first start are ignored, as are repeated starts or ends.
+pub fn intervals_for_day(day: &[TimeStamp]) -> (Vec<Item>, Option<Time>) {
+    let mut iter = day.iter();
+    let Some(mut start) = iter.find(|x| x.is_start()) else {
+        return (vec![], None);
+    };
+
+    let mut items = vec![];
+    while let Some(head) = iter.next() {
+        if head.is_start() {
+            start = head;
+        } else {
+            items.push(Item::between(start, head));
+            let Some(next) = iter.find(|x| x.is_start()) else {
+                return (items, None);
+            };
+            start = next;
+        }
+    }
+    (items, Some(start.time))
+}
+
+/// how a log is stored, chosen by the extension of its file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Json,
+    Toml,
+    Ron,
+}
+
+impl Format {
+    /// the format of the log at `path`, json unless it is named like a toml or ron file. The
+    /// `.gz` of a compressed log is skipped, so `hours.toml.gz` is toml
+    pub fn of(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let name = name.strip_suffix(".gz").unwrap_or(name);
+        match Path::new(name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("toml") => Format::Toml,
+            Some("ron") => Format::Ron,
+            _ => Format::Json,
+        }
+    }
+
+    /// the extension of files in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Toml => "toml",
+            Format::Ron => "ron",
+        }
+    }
+
+    fn parse(self, buf: &[u8]) -> anyhow::Result<Log> {
+        Ok(match self {
+            Format::Json => serde_json::from_slice(buf)?,
+            Format::Toml => toml::from_str(std::str::from_utf8(buf)?)?,
+            Format::Ron => ron::de::from_bytes(buf)?,
+        })
+    }
+
+    fn write(self, log: &Log, mut output: impl Write) -> anyhow::Result<()> {
+        match self {
+            Format::Json => serde_json::to_writer_pretty(output, log)?,
+            Format::Toml => output.write_all(toml::to_string_pretty(log)?.as_bytes())?,
+            Format::Ron => {
+                let config = ron::ser::PrettyConfig::default();
+                output.write_all(ron::ser::to_string_pretty(log, config)?.as_bytes())?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// whether a timestamp at `now` should move the timestamp `last` on `date` instead of being added.
+///
+/// That is the case if `last` is an end and `now` is at most the tolerance after it, which is
+/// `tolerance` or else the one `last` was recorded with
+pub fn should_merge(
+    last: &TimeStamp,
+    date: NaiveDate,
+    now: NaiveDateTime,
+    tolerance: Option<u32>,
+) -> bool {
+    let window = Duration::seconds(tolerance.unwrap_or(last.tolerance) as i64);
+    last.is_end() && now <= NaiveDateTime::new(date, last.time.0) + window
+}
+
+/// the contents of a gzip compressed log, which is recognized by its first bytes whatever its
+/// name is. Other contents are returned as they are
+pub fn decompress(buf: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    if !buf.starts_with(&GZIP_MAGIC) {
+        return Ok(buf);
+    }
+    let mut decompressed = Vec::new();
+    GzDecoder::new(&buf[..])
+        .read_to_end(&mut decompressed)
+        .context("the log looks gzip compressed but could not be decompressed")?;
+    Ok(decompressed)
+}
+
+/// whether the log at `path` is written gzip compressed, which is the case if it ends in .gz
+fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "gz")
+}
+
+/// how a project is referred to in output, the default project has an empty name
+pub fn display_project(project: &str) -> &str {
+    if project.is_empty() {
+        "(default)"
+    } else {
+        project
+    }
+}
+
+/// `-` as a log file refers to stdin
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-03-01 09:00 in UTC+1
+    fn clock() -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339("2024-03-01T09:00:00+01:00").unwrap()
+    }
+
+    /// an empty directory for a test to put logs in
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("record-hours-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn recorded() -> Record {
+        let mut record = Record::open(std::io::empty(), Format::Json, &clock()).unwrap();
+        record
+            .clock_in("work".to_string(), None, None, false)
+            .unwrap();
+        record
+    }
+
+    fn log(json: &str) -> Log {
+        Log::read(json.as_bytes(), Format::Json).unwrap()
+    }
+
+    /// the times of each day of `project`, as HH:MM
+    fn days(log: &Log, project: &str) -> Vec<(String, Vec<String>)> {
+        log.projects[project]
+            .entries
+            .iter()
+            .map(|(date, day)| {
+                (
+                    date.to_string(),
+                    day.iter().map(|t| t.time.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn localize_across_midnight() {
+        let mut log = log(r#"{
+                "late": {"2024-03-01": [
+                    {"type": "Start", "time": "22:00:00", "tolerance": 900, "utc_offset": 0},
+                    {"type": "End", "time": "23:45:00", "tolerance": 900, "utc_offset": 0}]},
+                "moved": {
+                    "2024-03-01": [
+                        {"type": "Start", "time": "23:30:00", "tolerance": 900, "utc_offset": 0},
+                        {"type": "End", "time": "23:50:00", "tolerance": 900, "utc_offset": 0}],
+                    "2024-03-02": [
+                        {"type": "Start", "time": "08:00:00", "tolerance": 900},
+                        {"type": "End", "time": "09:00:00", "tolerance": 900, "utc_offset": 3600}]}
+            }"#);
+        log.localize(&FixedOffset::east_opt(3600).unwrap());
+
+        // the end crosses midnight but stays behind its start
+        assert_eq!(
+            days(&log, "late"),
+            [("2024-03-01".into(), vec!["23:00".into(), "00:45".into()])]
+        );
+        let (items, open) = intervals_for_day(
+            &log.projects["late"].entries[&Date(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap())],
+        );
+        assert_eq!((items.len(), open), (1, None));
+        assert_eq!(items[0].duration(), Duration::minutes(105));
+
+        // the whole interval starts on the next day in UTC+1, timestamps without an offset are
+        // left as they are
+        assert_eq!(
+            days(&log, "moved"),
+            [(
+                "2024-03-02".into(),
+                vec![
+                    "00:30".into(),
+                    "00:50".into(),
+                    "08:00".into(),
+                    "09:00".into()
+                ]
+            )]
+        );
+    }
+
+    /// move the clock of `record` to `time` on march `day`, 2024
+    fn at(record: &mut Record, day: u32, time: &str) {
+        record.date = Date(NaiveDate::from_ymd_opt(2024, 3, day).unwrap());
+        record.time = time.parse().unwrap();
+        record.recorded_at = NaiveDateTime::new(record.date.0, record.time.0);
+    }
+
+    fn toggle(record: &mut Record, day: u32, time: &str) -> Insertion {
+        at(record, day, time);
+        record
+            .insert("work".to_string(), None, None, true, false)
+            .unwrap()
+    }
+
+    #[test]
+    fn close_across_midnight() {
+        let mut record = Record::open(std::io::empty(), Format::Json, &clock()).unwrap();
+        toggle(&mut record, 1, "23:30");
+        at(&mut record, 2, "00:10");
+        let date = Date(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        let start = Time("23:30".parse().unwrap());
+        assert_eq!(record.open_start("work"), Some((date, start)));
+
+        let insertion = toggle(&mut record, 2, "00:15");
+        assert!(matches!(insertion, Insertion::Stopped { start } if start.to_string() == "23:30"));
+        assert_eq!(stamps(&record.log, 1), ["+23:30", "-00:15"]);
+        assert!(!record.log.projects["work"]
+            .entries
+            .contains_key(&Date(NaiveDate::from_ymd_opt(2024, 3, 2).unwrap())));
+        let (items, open) = intervals_for_day(&record.log.projects["work"].entries[&date]);
+        assert_eq!((items[0].duration(), open), (Duration::minutes(45), None));
+        assert_eq!(record.open_start("work"), None);
+
+        let mut stopped = Record::open(std::io::empty(), Format::Json, &clock()).unwrap();
+        toggle(&mut stopped, 1, "23:30");
+        at(&mut stopped, 2, "00:15");
+        stopped.stop("work".to_string()).unwrap();
+        assert_eq!(stamps(&stopped.log, 1), ["+23:30", "-00:15"]);
+        assert!(stopped.stop("work".to_string()).is_err());
+
+        let mut out = Record::open(std::io::empty(), Format::Json, &clock()).unwrap();
+        toggle(&mut out, 1, "23:30");
+        at(&mut out, 2, "00:15");
+        assert_eq!(
+            out.clock_out("work".to_string(), None, None).unwrap(),
+            start
+        );
+        assert_eq!(stamps(&out.log, 1), ["+23:30", "-00:15"]);
+
+        // a day later the start is left open, closing it would be more than a day long
+        at(&mut record, 2, "23:00");
+        record
+            .clock_in("work".to_string(), None, None, false)
+            .unwrap();
+        toggle(&mut record, 3, "23:30");
+        assert_eq!(stamps(&record.log, 2), ["+23:00"]);
+        assert_eq!(stamps(&record.log, 3), ["+23:30"]);
+    }
+
+    #[test]
+    fn merge_after_midnight_keeps_pairs() {
+        let mut record = Record::open(std::io::empty(), Format::Json, &clock()).unwrap();
+        toggle(&mut record, 1, "23:00");
+        toggle(&mut record, 1, "23:58");
+        // within the tolerance of yesterday's end, which moves past midnight
+        let extended = toggle(&mut record, 2, "00:03");
+        assert!(matches!(extended, Insertion::Extended { .. }));
+        assert_eq!(
+            days(&record.log, "work"),
+            [("2024-03-01".into(), vec!["23:00".into(), "00:03".into()])]
+        );
+
+        // an earlier interval on the same day is imported, which sorts the day
+        let mut other = Record::open(std::io::empty(), Format::Json, &clock()).unwrap();
+        toggle(&mut other, 1, "08:00");
+        toggle(&mut other, 1, "09:00");
+        let other = other.log;
+        let copy = log(&serde_json::to_string(&record.log).unwrap());
+        record.import(other);
+        record.import(copy);
+
+        let day = &record.log.projects["work"].entries
+            [&Date(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap())];
+        assert_eq!(
+            days(&record.log, "work"),
+            [(
+                "2024-03-01".into(),
+                vec![
+                    "08:00".into(),
+                    "09:00".into(),
+                    "23:00".into(),
+                    "00:03".into()
+                ]
+            )]
+        );
+        let (items, open) = intervals_for_day(day);
+        assert_eq!(open, None);
+        let durations: Vec<_> = items.iter().map(Item::duration).collect();
+        assert_eq!(durations, [Duration::hours(1), Duration::minutes(63)]);
+    }
+
+    fn stamp(typ: TimeStampType, time: &str) -> TimeStamp {
+        TimeStamp {
+            typ,
+            time: time.parse().unwrap(),
+            tolerance: DEFAULT_TOLERANCE,
+            note: None,
+            utc_offset: None,
+            recorded_at: None,
+        }
+    }
+
+    /// a day from alternating `+HH:MM` starts and `-HH:MM` ends
+    fn day(times: &[&str]) -> Vec<TimeStamp> {
+        times
+            .iter()
+            .map(|time| match time.split_at(1) {
+                ("+", time) => stamp(TimeStampType::Start, time),
+                (_, time) => stamp(TimeStampType::End, time),
+            })
+            .collect()
+    }
+
+    fn intervals_of(times: &[&str]) -> (Vec<(String, String)>, Option<String>) {
+        let (items, open) = intervals_for_day(&day(times));
+        let items = items
+            .iter()
+            .map(|item| (item.start.to_string(), item.end.to_string()))
+            .collect();
+        (items, open.map(|start| start.to_string()))
+    }
+
+    fn pair(start: &str, end: &str) -> (String, String) {
+        (start.to_string(), end.to_string())
+    }
+
+    #[test]
+    fn intervals_of_empty_day() {
+        assert_eq!(intervals_of(&[]), (vec![], None));
+    }
+
+    #[test]
+    fn intervals_of_balanced_day() {
+        assert_eq!(
+            intervals_of(&["+09:00", "-12:00", "+13:00", "-17:30"]),
+            (vec![pair("09:00", "12:00"), pair("13:00", "17:30")], None)
+        );
+    }
+
+    #[test]
+    fn intervals_with_trailing_start() {
+        assert_eq!(
+            intervals_of(&["+09:00", "-12:00", "+13:00"]),
+            (vec![pair("09:00", "12:00")], Some("13:00".to_string()))
+        );
+        assert_eq!(
+            intervals_of(&["+09:00"]),
+            (vec![], Some("09:00".to_string()))
+        );
+    }
+
+    #[test]
+    fn intervals_ignore_leading_and_repeated_ends() {
+        assert_eq!(
+            intervals_of(&["-08:00", "+09:00", "-12:00", "-12:30"]),
+            (vec![pair("09:00", "12:00")], None)
+        );
+        assert_eq!(intervals_of(&["-08:00"]), (vec![], None));
+    }
+
+    #[test]
+    fn intervals_use_the_last_of_repeated_starts() {
+        assert_eq!(
+            intervals_of(&["+09:00", "+10:00", "-12:00"]),
+            (vec![pair("10:00", "12:00")], None)
+        );
+    }
+
+    #[test]
+    fn interval_notes() {
+        let mut day = day(&["+09:00", "-12:00", "+13:00", "-14:00"]);
+        day[1].note = Some("end".to_string());
+        day[2].note = Some("start".to_string());
+        day[3].note = Some("ignored".to_string());
+        let notes: Vec<_> = intervals_for_day(&day)
+            .0
+            .into_iter()
+            .map(|item| item.note)
+            .collect();
+        assert_eq!(notes, [Some("end".to_string()), Some("start".to_string())]);
+    }
+
+    /// the types and times of the timestamps on march `day`, like `+09:00` and `-12:00`
+    fn stamps(log: &Log, day: u32) -> Vec<String> {
+        let date = Date(NaiveDate::from_ymd_opt(2024, 3, day).unwrap());
+        log.projects["work"].entries[&date]
+            .iter()
+            .map(|t| format!("{}{}", if t.is_start() { "+" } else { "-" }, t.time))
+            .collect()
+    }
+
+    #[test]
+    fn backfill_keeps_day_sorted() {
+        let mut record = Record::open(std::io::empty(), Format::Json, &clock()).unwrap();
+        toggle(&mut record, 1, "13:00");
+        toggle(&mut record, 1, "14:00");
+        at(&mut record, 1, "09:00");
+        record
+            .clock_in("work".to_string(), None, None, false)
+            .unwrap();
+        at(&mut record, 1, "10:00");
+        record.clock_out("work".to_string(), None, None).unwrap();
+
+        assert_eq!(
+            stamps(&record.log, 1),
+            ["+09:00", "-10:00", "+13:00", "-14:00"]
+        );
+        let date = Date(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        let (items, open) = intervals_for_day(&record.log.projects["work"].entries[&date]);
+        assert_eq!(items.len(), 2);
+        assert_eq!(open, None);
+    }
+
+    #[test]
+    fn default_tolerance_is_for_new_timestamps() {
+        let mut record = Record::open(std::io::empty(), Format::Json, &clock()).unwrap();
+        toggle(&mut record, 1, "09:00");
+        toggle(&mut record, 1, "12:00");
+        record.tolerance = 60;
+        // the end keeps the window it was recorded with
+        toggle(&mut record, 1, "12:10");
+        assert_eq!(stamps(&record.log, 1), ["+09:00", "-12:10"]);
+
+        toggle(&mut record, 1, "13:00");
+        toggle(&mut record, 1, "14:00");
+        toggle(&mut record, 1, "14:02");
+        assert_eq!(
+            stamps(&record.log, 1),
+            ["+09:00", "-12:10", "+13:00", "-14:00", "+14:02"]
+        );
+        let date = Date(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        let tolerances: Vec<_> = record.log.projects["work"].entries[&date]
+            .iter()
+            .map(|t| t.tolerance)
+            .collect();
+        assert_eq!(tolerances, [900, 900, 60, 60, 60]);
+
+        // a given tolerance is recorded with the end, so it decides the next merge
+        at(&mut record, 1, "14:30");
+        record
+            .insert("work".to_string(), Some(3600), None, true, false)
+            .unwrap();
+        toggle(&mut record, 1, "14:50");
+        assert_eq!(
+            stamps(&record.log, 1),
+            ["+09:00", "-12:10", "+13:00", "-14:00", "+14:02", "-14:50"]
+        );
+    }
+
+    #[test]
+    fn failed_save_keeps_original() {
+        let dir = temp_dir("failed-save");
+        let path = dir.join("hours.json");
+        std::fs::write(&path, "{}").unwrap();
+        // the temporary file can't be created where a directory is in the way, which fails even
+        // for root unlike a read-only directory
+        std::fs::create_dir(dir.join(".hours.json.tmp")).unwrap();
+
+        assert!(recorded().save(&path, 1).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+        assert!(!dir.join("hours.json.1").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// a day from 09:00 to 12:00 and a clock in at 12:05 with or without `merge`
+    fn record_after_break(merge: bool) -> (Record, Insertion) {
+        let mut record = Record::open(std::io::empty(), Format::Json, &clock()).unwrap();
+        toggle(&mut record, 1, "09:00");
+        toggle(&mut record, 1, "12:00");
+        at(&mut record, 1, "12:05");
+        let insertion = record
+            .insert("work".to_string(), None, None, merge, false)
+            .unwrap();
+        (record, insertion)
+    }
+
+    #[test]
+    fn merge_bridges_gap() {
+        let (record, insertion) = record_after_break(true);
+        assert!(
+            matches!(insertion, Insertion::Extended { previous, .. } if previous.to_string() == "12:00")
+        );
+        assert_eq!(stamps(&record.log, 1), ["+09:00", "-12:05"]);
+    }
+
+    #[test]
+    fn no_merge_starts_new_interval() {
+        let (record, insertion) = record_after_break(false);
+        assert!(matches!(insertion, Insertion::Started));
+        assert_eq!(stamps(&record.log, 1), ["+09:00", "-12:00", "+12:05"]);
+    }
+
+    /// clock in at `time` on 2024-03-01 after an interval that ended at 12:00 with the default
+    /// tolerance of 15 minutes
+    fn clock_in_at(time: &str) -> Insertion {
+        let log = r#"{"work": {"2024-03-01": [
+            {"type": "Start", "time": "09:00:00", "tolerance": 900},
+            {"type": "End", "time": "12:00:00", "tolerance": 900}]}}"#;
+        let clock = DateTime::parse_from_rfc3339(&format!("2024-03-01T{time}+01:00")).unwrap();
+        let mut record = Record::open(log.as_bytes(), Format::Json, &clock).unwrap();
+        record
+            .insert("work".to_string(), None, None, true, false)
+            .unwrap()
+    }
+
+    #[test]
+    fn tolerance_boundary() {
+        assert!(matches!(
+            clock_in_at("12:14:59"),
+            Insertion::Extended { .. }
+        ));
+        assert!(matches!(
+            clock_in_at("12:15:00"),
+            Insertion::Extended { .. }
+        ));
+        assert!(matches!(clock_in_at("12:15:01"), Insertion::Started));
+    }
+
+    #[test]
+    fn my_duration() {
+        let show = |duration| MyDuration(duration).to_string();
+        assert_eq!(show(Duration::zero()), "0min");
+        assert_eq!(show(Duration::minutes(45)), "45min");
+        assert_eq!(show(Duration::hours(1)), "1h");
+        assert_eq!(show(Duration::minutes(-90)), "-1h30min");
+        assert_eq!(show(Duration::minutes(-45)), "-45min");
+        // less than a minute is not worth a sign
+        assert_eq!(show(Duration::seconds(-30)), "0min");
+    }
+
+    #[test]
+    fn durations_leave_out_zero_parts() {
+        let show = |duration| MyDuration(duration).to_string();
+        assert_eq!(show(Duration::hours(2)), "2h");
+        assert_eq!(show(Duration::minutes(45)), "45min");
+        assert_eq!(show(Duration::minutes(165)), "2h45min");
+        assert_eq!(show(Duration::zero()), "0min");
+
+        let show = |duration| SecondsDuration(duration).to_string();
+        assert_eq!(show(Duration::seconds(3903)), "1h5min3s");
+        assert_eq!(show(Duration::hours(2)), "2h");
+        assert_eq!(show(Duration::seconds(3)), "3s");
+        assert_eq!(show(Duration::zero()), "0s");
+        assert_eq!(show(Duration::seconds(-65)), "-1min5s");
+    }
+
+    #[test]
+    fn start_before_last_end_is_refused() {
+        let mut record = Record::open(std::io::empty(), Format::Json, &clock()).unwrap();
+        toggle(&mut record, 1, "09:00");
+        toggle(&mut record, 1, "17:00");
+        at(&mut record, 1, "08:00");
+        let error = record
+            .insert("work".to_string(), None, None, true, false)
+            .unwrap_err();
+        assert!(error.to_string().contains("--force"), "{error}");
+        assert_eq!(stamps(&record.log, 1), ["+09:00", "-17:00"]);
+
+        // with force it is put in order anyway
+        record
+            .insert("work".to_string(), None, None, true, true)
+            .unwrap();
+        assert_eq!(stamps(&record.log, 1), ["+08:00", "+09:00", "-17:00"]);
+    }
+
+    #[test]
+    fn should_merge_boundary() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let end = stamp(TimeStampType::End, "12:00");
+        let now =
+            |time| NaiveDateTime::new(date, NaiveTime::parse_from_str(time, "%H:%M:%S").unwrap());
+
+        assert!(should_merge(&end, date, now("12:14:59"), None));
+        assert!(should_merge(&end, date, now("12:15:00"), None));
+        assert!(!should_merge(&end, date, now("12:15:01"), None));
+        // a given tolerance replaces the one of the end
+        assert!(should_merge(&end, date, now("12:30:00"), Some(1800)));
+        assert!(!should_merge(&end, date, now("12:30:01"), Some(1800)));
+        // only ends are moved
+        assert!(!should_merge(
+            &stamp(TimeStampType::Start, "12:00"),
+            date,
+            now("12:00:01"),
+            None
+        ));
+    }
+
+    #[test]
+    fn import_into_repeated_timestamps() {
+        let mut record = Record::open(
+            r#"{"work": {"2024-03-01": [
+                {"type": "Start", "time": "09:00:00", "tolerance": 900},
+                {"type": "Start", "time": "09:00:00", "tolerance": 900}]}}"#
+                .as_bytes(),
+            Format::Json,
+            &clock(),
+        )
+        .unwrap();
+        assert_eq!(
+            record.import(log(r#"{"work": {"2024-03-01": []}}"#)),
+            (0, 0, 0)
+        );
+        assert_eq!(stamps(&record.log, 1), ["+09:00"]);
+
+        let other = log(r#"{"work": {"2024-03-01": [
+            {"type": "End", "time": "10:00:00", "tolerance": 900},
+            {"type": "End", "time": "10:00:00", "tolerance": 900}]}}"#);
+        assert_eq!(record.import(other), (0, 0, 1));
+        assert_eq!(stamps(&record.log, 1), ["+09:00", "-10:00"]);
+    }
+
+    /// the duration from `start` to `end`, recorded in the given offsets from UTC in hours
+    fn duration_across(start: (&str, i32), end: (&str, i32)) -> Duration {
+        let mut day = day(&[start.0, end.0]);
+        day[0].utc_offset = Some(start.1 * 3600);
+        day[1].utc_offset = Some(end.1 * 3600);
+        let (items, _) = intervals_for_day(&day);
+        items[0].duration()
+    }
+
+    #[test]
+    fn duration_across_offset_change() {
+        // the clocks skip from 02:00 to 03:00 when daylight saving time starts
+        assert_eq!(
+            duration_across(("+01:30", 1), ("-03:30", 2)),
+            Duration::hours(1)
+        );
+        // and go from 03:00 back to 02:00 when it ends
+        assert_eq!(
+            duration_across(("+01:30", 2), ("-02:30", 1)),
+            Duration::hours(2)
+        );
+        assert_eq!(
+            duration_across(("+02:30", 2), ("-02:10", 1)),
+            Duration::minutes(40)
+        );
+        // crossing midnight as well
+        assert_eq!(
+            duration_across(("+23:30", 1), ("-03:30", 2)),
+            Duration::hours(3)
+        );
+        assert_eq!(
+            duration_across(("+09:00", 2), ("-17:00", 2)),
+            Duration::hours(8)
+        );
+    }
+
+    #[test]
+    fn spring_forward_localized() {
+        let mut log = log(r#"{"w": {"2024-03-31": [
+                {"type": "Start", "time": "01:30:00", "tolerance": 900, "utc_offset": 3600},
+                {"type": "End", "time": "03:30:00", "tolerance": 900, "utc_offset": 7200}]}}"#);
+        let date = Date(NaiveDate::from_ymd_opt(2024, 3, 31).unwrap());
+        let duration =
+            |log: &Log| intervals_for_day(&log.projects["w"].entries[&date]).0[0].duration();
+        // in the timezone that recorded them nothing is converted
+        assert_eq!(duration(&log), Duration::hours(1));
+        log.localize(&FixedOffset::east_opt(0).unwrap());
+        assert_eq!(duration(&log), Duration::hours(1));
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        let dir = temp_dir("gzip");
+        let path = dir.join("hours.json.gz");
+        let record = recorded();
+        record.save(&path, 0).unwrap();
+
+        let written = std::fs::read(&path).unwrap();
+        assert!(written.starts_with(&GZIP_MAGIC));
+        let read = Log::read(&written[..], Format::Json).unwrap();
+        assert_eq!(
+            read.projects["work"].entries,
+            record.log.projects["work"].entries
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_gzip() {
+        assert!(Log::read(&[0x1f, 0x8b, 0, 0][..], Format::Json).is_err());
+    }
+
+    #[test]
+    fn format_of() {
+        assert_eq!(Format::of(Path::new("hours.log.json")), Format::Json);
+        assert_eq!(Format::of(Path::new("hours")), Format::Json);
+        assert_eq!(Format::of(Path::new("dir.toml/hours")), Format::Json);
+        assert_eq!(Format::of(Path::new("hours.toml")), Format::Toml);
+        assert_eq!(Format::of(Path::new("hours.ron.gz")), Format::Ron);
+    }
+
+    /// save a log with the features that need care in other formats and read it back
+    fn round_trip(name: &str) {
+        let dir = temp_dir(name);
+        let path = dir.join(name);
+        let mut record = recorded();
+        record.time = Time(NaiveTime::from_hms_opt(12, 0, 0).unwrap());
+        record
+            .clock_out("work".to_string(), None, Some("a \"note\"".to_string()))
+            .unwrap();
+        record.clock_in(String::new(), None, None, false).unwrap();
+        record.mark("work".to_string(), "release".to_string());
+        record.log.projects.get_mut("work").unwrap().billable = Some(false);
+        record.save(&path, 0).unwrap();
+
+        let read = Log::read(File::open(&path).unwrap(), Format::of(&path)).unwrap();
+        assert_eq!(read.projects.len(), 2);
+        for (name, project) in &record.log.projects {
+            let other = &read.projects[name];
+            assert_eq!(other.entries, project.entries);
+            assert_eq!(other.billable, project.billable);
+            assert_eq!(other.events, project.events);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn json_round_trip() {
+        round_trip("hours.json");
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        round_trip("hours.toml");
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        round_trip("hours.ron");
+    }
+}
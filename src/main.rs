@@ -1,354 +1,1794 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fmt::Display,
     fs::File,
-    io::{Read, Write},
-    path::PathBuf,
-    str::FromStr,
+    io::IsTerminal,
+    path::{Path, PathBuf},
 };
 
 use anyhow::{anyhow, Context};
-use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
-use clap::{Parser, Subcommand};
-use log::warn;
-use serde::{Deserialize, Serialize};
+use chrono::{
+    DateTime, Datelike, Days, Duration, FixedOffset, Local, Months, NaiveDate, NaiveDateTime,
+    NaiveTime, Offset, TimeZone, Timelike, Weekday,
+};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum};
+use config::Config;
+use export::ExportFormat;
+use log::LevelFilter;
+use record_hours::{
+    decompress, display_project, intervals_for_day, is_stdin, Clock, Date, DecimalDuration, Event,
+    Format, Insertion, Item, LocalClock, Log, MyDuration, Record, Time, TimeStamp,
+};
+use serde::Serialize;
 
+mod completions;
+mod config;
+mod export;
 mod format;
+mod report;
+mod show;
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
-struct Date(NaiveDate);
+#[derive(Debug, Clone, Parser)]
+pub struct App {
+    /// which file to record the hours in.
+    ///
+    /// Defaults to $RECORD_HOURS_FILE, then the log_file of the config, then the first
+    /// hours.log.json that exists in the working directory, $XDG_DATA_HOME/record-hours or the
+    /// home directory.
+    ///
+    /// The log is stored as json, or as toml or ron if the file ends in .toml or .ron. With
+    /// another .gz at the end, like hours.log.json.gz, it is gzip compressed.
+    ///
+    /// `-` reads the log from stdin, this only works for commands that don't modify the log, like
+    /// show. record and the other commands that write the log back reject it
+    #[clap(short, long)]
+    file: Option<PathBuf>,
+    /// use the log file of this profile from the config instead of --file
+    #[clap(long, conflicts_with = "file")]
+    profile: Option<String>,
+    /// log more, may be repeated: info, debug, trace. warnings are logged by default.
+    /// $RUST_LOG takes precedence
+    #[clap(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    /// don't log anything, not even errors
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// act as if it was this local time (YYYY-MM-DDTHH:MM) instead of now, e.g. to backfill
+    #[clap(long, global = true, value_parser = parse_now)]
+    now: Option<DateTime<FixedOffset>>,
+    #[clap(subcommand)]
+    command: Commands,
+}
 
-impl FromStr for Date {
-    type Err = chrono::ParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let inner = NaiveDate::parse_from_str(s, "%Y-%m-%d")?;
-        Ok(Date(inner))
-    }
+#[derive(Debug, Clone, Subcommand)]
+pub enum Commands {
+    Record {
+        #[clap(short, long)]
+        auto: bool,
+        #[clap(short, long)]
+        project: Option<String>,
+        /// how long after a stop a new record still merges into it, in seconds.
+        ///
+        /// The tolerance is stored with each timestamp, so existing entries keep the
+        /// window they were recorded with. Defaults to 15 minutes
+        #[clap(short, long)]
+        tolerance: Option<u32>,
+        /// record at this time (HH:MM) instead of now
+        #[clap(long)]
+        at: Option<Time>,
+        /// record on this date (YYYY-MM-DD) instead of today
+        #[clap(long)]
+        date: Option<Date>,
+        /// attach a note to the recorded timestamp, e.g. what you worked on
+        #[clap(short, long)]
+        note: Option<String>,
+        /// always add a new timestamp, even if the last stop is within the tolerance
+        #[clap(long)]
+        no_merge: bool,
+        /// only print what would be recorded, without changing the log
+        #[clap(long)]
+        dry_run: bool,
+        /// record a time before the last timestamp of the day, even though that leaves two starts
+        /// or two ends in a row for check to report
+        #[clap(long)]
+        force: bool,
+    },
+    /// like record, but report whether an interval was started or stopped
+    Toggle {
+        #[clap(short, long)]
+        project: Option<String>,
+    },
+    /// record that something happened now, like a release, shown between the intervals
+    Mark {
+        #[clap(short, long)]
+        project: Option<String>,
+        label: String,
+    },
+    /// clock in, fails instead of stopping if the project is already clocked in
+    In {
+        #[clap(short, long)]
+        project: Option<String>,
+        /// clock in at this time (HH:MM) instead of now
+        #[clap(long)]
+        at: Option<Time>,
+        /// close the open interval and start a new one instead of failing
+        #[clap(long)]
+        force: bool,
+    },
+    /// clock out, fails if the project is not clocked in
+    Out {
+        #[clap(short, long)]
+        project: Option<String>,
+        /// clock out at this time (HH:MM) instead of now
+        #[clap(long)]
+        at: Option<Time>,
+    },
+    /// clock in to the project that was worked on last
+    Continue,
+    /// record an interval of a known length that ends now
+    Add {
+        #[clap(short, long)]
+        project: Option<String>,
+        /// how long the interval was, e.g. 45m, 1h30m or 1.5h
+        #[clap(value_parser = parse_duration)]
+        duration: Duration,
+        /// record on this date (YYYY-MM-DD) instead of today
+        #[clap(long)]
+        date: Option<Date>,
+        /// attach a note to the interval
+        #[clap(short, long)]
+        note: Option<String>,
+    },
+    /// close the currently open interval
+    Stop {
+        #[clap(short, long)]
+        project: Option<String>,
+    },
+    /// show whether a project is currently clocked in
+    Status {
+        #[clap(short, long)]
+        project: Option<String>,
+    },
+    Show(show::ShowArgs),
+    /// total completed intervals per day, week or month
+    Summary {
+        #[clap(short, long)]
+        project: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = Period::Week)]
+        by: Period,
+        /// daily target, compared to the target times the number of recorded days of each period
+        #[clap(long, value_parser = parse_duration)]
+        target: Option<Duration>,
+    },
+    /// total completed intervals per project, most worked on first
+    Totals {
+        /// only count days on or after this date (YYYY-MM-DD)
+        #[clap(long)]
+        from: Option<Date>,
+        /// only count days since 7d, 2w or 1m ago, or since the start of today, week or month
+        #[clap(long, conflicts_with = "from", value_parser = parse_since)]
+        since: Option<Since>,
+        /// only count days on or before this date (YYYY-MM-DD)
+        #[clap(long)]
+        to: Option<Date>,
+        /// display time in decimal format: e.g. 1 hour, 45 minutes = 1.75
+        #[clap(short, long)]
+        decimal: bool,
+        /// combine projects named like client/frontend into groups of this many levels
+        #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+        depth: Option<u64>,
+    },
+    /// print only the total of the completed intervals, in minutes or decimal hours, for scripts
+    Total {
+        #[clap(short, long)]
+        project: Option<String>,
+        /// only count days on or after this date (YYYY-MM-DD)
+        #[clap(long)]
+        from: Option<Date>,
+        /// only count days on or before this date (YYYY-MM-DD)
+        #[clap(long)]
+        to: Option<Date>,
+        /// print decimal hours like 42.50 instead of whole minutes
+        #[clap(short, long)]
+        decimal: bool,
+    },
+    /// print the number of sessions and their longest, shortest, mean and median duration
+    Stats {
+        #[clap(short, long)]
+        project: Option<String>,
+        /// only count days on or after this date (YYYY-MM-DD)
+        #[clap(long)]
+        from: Option<Date>,
+        /// only count days on or before this date (YYYY-MM-DD)
+        #[clap(long)]
+        to: Option<Date>,
+    },
+    /// print csv with the decimal hours of every project per ISO week, e.g. for an accountant
+    Matrix {
+        /// only count days on or after this date (YYYY-MM-DD)
+        #[clap(long)]
+        from: Option<Date>,
+        /// only count days on or before this date (YYYY-MM-DD)
+        #[clap(long)]
+        to: Option<Date>,
+    },
+    /// report malformed timestamp sequences, fails if any are found
+    Check {
+        /// only check this project instead of all of them
+        #[clap(short, long)]
+        project: Option<String>,
+    },
+    /// list all projects in the log
+    List {
+        #[clap(short, long, value_enum, default_value_t = ListSort::Name)]
+        sort: ListSort,
+    },
+    /// remove a project and all of its entries
+    Delete {
+        project: String,
+        /// don't ask for confirmation
+        #[clap(short, long, visible_alias = "force")]
+        yes: bool,
+    },
+    /// mark a project as billable or not, totals then shows both sums
+    #[clap(group(clap::ArgGroup::new("class").required(true)))]
+    Tag {
+        #[clap(short, long)]
+        project: Option<String>,
+        #[clap(long, group = "class")]
+        billable: bool,
+        #[clap(long, group = "class")]
+        non_billable: bool,
+    },
+    /// rename a project, merging it into `to` if that already exists
+    Rename {
+        from: String,
+        to: String,
+        /// don't ask for confirmation
+        #[clap(short, long, visible_alias = "force")]
+        yes: bool,
+    },
+    /// move the entries of one day to another project, merging them if it has entries that day
+    Move {
+        /// the day to move (YYYY-MM-DD)
+        date: Date,
+        from: String,
+        to: String,
+        /// don't ask for confirmation
+        #[clap(short, long, visible_alias = "force")]
+        yes: bool,
+    },
+    /// break the interval that contains a time into two
+    Split {
+        #[clap(short, long)]
+        project: Option<String>,
+        /// the day of the interval (YYYY-MM-DD)
+        date: Date,
+        /// where to split the interval (HH:MM)
+        at: Time,
+    },
+    /// permanently remove intervals shorter than a threshold
+    Prune {
+        #[clap(short, long)]
+        project: Option<String>,
+        /// remove intervals shorter than this many minutes
+        #[clap(short, long, value_name = "MINUTES")]
+        min_duration: u64,
+        /// only report what would be removed
+        #[clap(long)]
+        dry_run: bool,
+        /// don't ask for confirmation
+        #[clap(short, long, visible_alias = "force")]
+        yes: bool,
+    },
+    /// render an html timesheet
+    Report {
+        #[clap(short, long)]
+        project: Option<String>,
+        /// write the report to this file instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// open the log in $VISUAL or $EDITOR and save it if it is still valid
+    Edit,
+    /// merge the entries of another log file into this one
+    Import {
+        from: PathBuf,
+        /// don't ask for confirmation
+        #[clap(short, long, visible_alias = "force")]
+        yes: bool,
+    },
+    /// print a completion script for a shell
+    #[clap(hide = true)]
+    Completions {
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// print every completed interval in a machine readable format
+    Export {
+        #[clap(short, long)]
+        project: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+    },
 }
 
-impl Display for Date {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)?;
-        Ok(())
-    }
+/// order of the projects printed by `list`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ListSort {
+    Name,
+    /// most days first
+    Days,
+    /// most hours first
+    Hours,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
-struct Time(NaiveTime);
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Period {
+    Day,
+    /// ISO week
+    Week,
+    Month,
+}
 
-impl Display for Time {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0.format("%H:%M"))?;
-        Ok(())
+impl Period {
+    /// label of the period that `date` falls into, sorts chronologically
+    fn label(&self, date: &Date) -> String {
+        match self {
+            Period::Day => date.to_string(),
+            Period::Week => date.0.format("%G-W%V").to_string(),
+            Period::Month => date.0.format("%Y-%m").to_string(),
+        }
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Default)]
-#[serde(transparent)]
-pub struct Log {
-    projects: BTreeMap<String, Project>,
+/// a relative range: a number of days, weeks or months ago, or the start of today, this week or
+/// this month
+#[derive(Debug, Clone, Copy)]
+pub enum Since {
+    Today,
+    Week,
+    Month,
+    Days(u64),
+    Months(u32),
 }
 
-#[derive(Deserialize, Serialize, Debug, Default)]
-#[serde(transparent)]
-pub struct Project {
-    entries: BTreeMap<Date, Vec<TimeStamp>>,
+impl Since {
+    /// the first day of the range if it is `today`
+    fn start(self, today: NaiveDate) -> anyhow::Result<Date> {
+        let date = match self {
+            Since::Today => Some(today),
+            Since::Week => {
+                today.checked_sub_days(Days::new(today.weekday().num_days_from_monday() as u64))
+            }
+            Since::Month => today.with_day(1),
+            Since::Days(days) => today.checked_sub_days(Days::new(days)),
+            Since::Months(months) => today.checked_sub_months(Months::new(months)),
+        };
+        date.map(Date).ok_or(anyhow!("{self:?} is too long ago"))
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct Entry {
-    date: Date,
-    timestamps: Vec<TimeStamp>,
+/// parse a relative range like 7d, 2w, 1m, today, week or month
+fn parse_since(s: &str) -> anyhow::Result<Since> {
+    match s {
+        "today" => Ok(Since::Today),
+        "week" => Ok(Since::Week),
+        "month" => Ok(Since::Month),
+        _ => {
+            let split = s.char_indices().last().map_or(0, |(i, _)| i);
+            let (count, unit) = s.split_at(split);
+            let count: u32 = count.parse().map_err(|_| {
+                anyhow!("invalid range {s:?}, use e.g. 7d, 2w, 1m, today, week or month")
+            })?;
+            match unit {
+                "d" => Ok(Since::Days(count as u64)),
+                "w" => Ok(Since::Days(count as u64 * 7)),
+                "m" => Ok(Since::Months(count)),
+                _ => Err(anyhow!("unknown unit {unit:?} in {s:?}, use d, w or m")),
+            }
+        }
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct TimeStamp {
-    #[serde(rename = "type")]
-    typ: TimeStampType,
-    time: Time,
-    /// tolerance (for how to merge entries) in seconds
-    tolerance: u32,
+/// parse the local time given with --now
+fn parse_now(s: &str) -> anyhow::Result<DateTime<FixedOffset>> {
+    let now = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M")
+        .map_err(|_| anyhow!("invalid time {s:?}, use YYYY-MM-DDTHH:MM"))?;
+    Local
+        .from_local_datetime(&now)
+        .earliest()
+        .map(|now| now.fixed_offset())
+        .ok_or(anyhow!("{s:?} does not exist in the local time zone"))
 }
 
-impl TimeStamp {
-    fn is_start(&self) -> bool {
-        self.typ == TimeStampType::Start
+/// the clock of the whole run: the system time, or the time given with --now
+#[derive(Debug, Clone, Copy)]
+struct Now(Option<DateTime<FixedOffset>>);
+
+impl Clock for Now {
+    fn now(&self) -> DateTime<FixedOffset> {
+        self.0.unwrap_or_else(|| LocalClock.now())
     }
+}
 
-    fn is_end(&self) -> bool {
-        self.typ == TimeStampType::End
+/// parse a duration made of numbers with a unit, like 45m, 1h30m or 1.5h
+fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let mut seconds = 0.0;
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(anyhow!("empty duration"));
+    }
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or(anyhow!("missing unit in {s:?}, use h, m or s"))?;
+        let value: f64 = rest[..split]
+            .parse()
+            .map_err(|_| anyhow!("invalid number in {s:?}"))?;
+        let unit = rest[split..].chars().next().unwrap();
+        seconds += value
+            * match unit {
+                'h' => 3600.0,
+                'm' => 60.0,
+                's' => 1.0,
+                _ => return Err(anyhow!("unknown unit {unit:?} in {s:?}, use h, m or s")),
+            };
+        rest = &rest[split + unit.len_utf8()..];
     }
+    // the cast saturates, try_seconds then rejects it
+    seconds
+        .is_finite()
+        .then(|| Duration::try_seconds(seconds.round() as i64))
+        .flatten()
+        .ok_or(anyhow!("duration {s:?} is too long"))
 }
 
-#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub enum TimeStampType {
-    Start,
-    End,
+/// the --min-duration of prune
+fn prune_minutes(minutes: u64) -> anyhow::Result<Duration> {
+    i64::try_from(minutes)
+        .ok()
+        .and_then(Duration::try_minutes)
+        .ok_or(anyhow!("--min-duration {minutes} is too long"))
 }
 
-#[derive(Debug, Clone, Parser)]
-pub struct App {
-    /// which file to record the hours in
-    #[clap(short, long)]
-    file: Option<PathBuf>,
-    #[clap(subcommand)]
-    command: Commands,
+/// inclusive range of dates, either bound may be open
+#[derive(Debug, Clone, Copy, Default)]
+struct DateRange {
+    from: Option<Date>,
+    to: Option<Date>,
 }
 
-#[derive(Debug, Clone, Subcommand)]
-pub enum Commands {
-    Record {
-        #[clap(short, long)]
-        auto: bool,
-        #[clap(short, long)]
-        project: Option<String>,
-    },
-    Show {
-        /// show for specific project.
-        ///
-        /// May be repeated.
-        ///
-        /// --project "" shows only the default project
-        #[clap(short, long)]
-        project: Vec<String>,
-        /// display time in decimal format: e.g. 1 hour, 45 minutes = 1.75
-        #[clap(short, long)]
-        decimal: bool,
-        /// Display format.
-        ///
-        /// Variables that can be used for expansion:
-        /// %d => date in y-m-d
-        /// %Y => year
-        /// %M => month
-        /// %D => day
-        /// %t => decimal time that has been recorded
-        /// %h => hours that have been recorded
-        /// %m => minutes that have been recorded
-        /// %P => the project
-        /// %% => a literal '%'
-        #[clap(short, long)]
-        format: Option<String>,
-    },
+impl DateRange {
+    fn new(from: Option<Date>, to: Option<Date>) -> anyhow::Result<Self> {
+        if let (Some(from), Some(to)) = (from, to) {
+            if from > to {
+                return Err(anyhow!("--from {from} is after --to {to}"));
+            }
+        }
+        Ok(Self { from, to })
+    }
+
+    fn contains(&self, date: &Date) -> bool {
+        self.from.is_none_or(|from| from <= *date) && self.to.is_none_or(|to| *date <= to)
+    }
 }
 
-struct Record {
-    log: Log,
-    date: Date,
-    time: Time,
+/// which days `show` visits and how their durations are computed
+#[derive(Debug, Clone, Copy, Default)]
+struct ShowOptions {
+    range: DateRange,
+    /// round each interval to a multiple of this many minutes
+    round: Option<u32>,
+    /// only visit the last this many days of the range
+    tail: Option<usize>,
+    /// drop intervals shorter than this many minutes
+    min_duration: Option<u32>,
+    /// round the day total to the nearest whole hour
+    round_day: bool,
+    /// snap the interval times outwards to multiples of this many minutes
+    snap: Option<u32>,
+    /// join intervals separated by less than this many seconds
+    merge_gaps: Option<u32>,
+    /// also visit the days of the range without any entries
+    show_empty: bool,
+    /// leave out saturdays and sundays without entries when visiting empty days
+    skip_weekends: bool,
+    /// also visit the days that only have events
+    events: bool,
+    /// visit the most recent day first
+    reverse: bool,
+    /// visit the breaks between the intervals instead of the intervals
+    invert: bool,
+    /// treat projects as groups of this many `/` separated levels
+    depth: Option<usize>,
 }
 
-impl Record {
-    fn open(mut input: impl Read) -> anyhow::Result<Self> {
-        let mut buf = Vec::new();
-        input.read_to_end(&mut buf)?;
+impl ShowOptions {
+    fn item_duration(&self, item: &Item) -> Duration {
+        let duration = item.duration();
+        match self.round {
+            Some(minutes) if minutes > 0 => {
+                let step = minutes as i64 * 60;
+                let seconds = duration.num_seconds();
+                Duration::seconds((seconds + step / 2) / step * step)
+            }
+            _ => duration,
+        }
+    }
 
-        let log: Log = if buf.is_empty() {
-            log::warn!("file was empty, using default");
-            Log::default()
-        } else {
-            serde_json::from_slice(&buf)?
+    /// move the start of `item` back and its end forward to the next multiple of `snap` minutes
+    fn snap_item(&self, item: &mut Item) {
+        let Some(step) = self
+            .snap
+            .filter(|&minutes| minutes > 0)
+            .and_then(|minutes| minutes.checked_mul(60))
+        else {
+            return;
         };
+        let start = item.start.0.num_seconds_from_midnight() / step * step;
+        let mut end = item.end.0.num_seconds_from_midnight().div_ceil(step) * step;
+        if item.end < item.start {
+            end += 24 * 60 * 60;
+        }
+        // an end snapped to midnight wraps to 00:00, the correction keeps its duration
+        item.start = Time(NaiveTime::MIN + Duration::seconds(start as i64));
+        item.end = Time(NaiveTime::MIN + Duration::seconds(end as i64));
+        let snapped = Duration::seconds(end as i64 - start as i64);
+        item.correction += snapped - (item.duration() - item.correction);
+    }
+
+    /// join consecutive intervals whose gap is shorter than `merge_gaps` seconds, keeping both notes
+    fn merge_gaps(&self, items: Vec<Item>) -> Vec<Item> {
+        let Some(seconds) = self.merge_gaps else {
+            return items;
+        };
+        let gap = Duration::seconds(seconds as i64);
+        let mut merged: Vec<Item> = Vec::with_capacity(items.len());
+        for item in items {
+            match merged.last_mut() {
+                // overlapping intervals and ones crossing midnight are left alone
+                Some(last)
+                    if last.start <= last.end
+                        && last.end <= item.start
+                        && item.start.0 - last.end.0 < gap =>
+                {
+                    last.end = item.end;
+                    last.correction += item.correction;
+                    last.note = match (last.note.take(), item.note) {
+                        (Some(first), Some(second)) => Some(format!("{first}; {second}")),
+                        (first, second) => first.or(second),
+                    };
+                }
+                _ => merged.push(item),
+            }
+        }
+        merged
+    }
+
+    /// remove the intervals that are too short to be shown, returns how many were removed
+    fn filter_items(&self, items: &mut Vec<Item>) -> usize {
+        let Some(minutes) = self.min_duration else {
+            return 0;
+        };
+        let before = items.len();
+        items.retain(|item| item.duration() >= Duration::minutes(minutes as i64));
+        before - items.len()
+    }
+
+    fn day_duration(&self, items: &[Item]) -> Duration {
+        let duration: Duration = items.iter().map(|item| self.item_duration(item)).sum();
+        if self.round_day {
+            let hour = 60 * 60;
+            Duration::seconds((duration.num_seconds() + hour / 2) / hour * hour)
+        } else {
+            duration
+        }
+    }
+}
+
+/// describe everything that is wrong with the sequence of timestamps of a single day
+fn check_day(day: &[TimeStamp], is_past: bool) -> Vec<String> {
+    let mut problems = vec![];
+    if let Some(first) = day.first().filter(|x| x.is_end()) {
+        problems.push(format!("starts with an end at {}", first.time));
+    }
+    for pair in day.windows(2) {
+        if pair[0].typ == pair[1].typ {
+            problems.push(format!(
+                "two consecutive {:?}s at {} and {}",
+                pair[0].typ, pair[0].time, pair[1].time
+            ));
+        }
+    }
+    if let Some(last) = day.last().filter(|x| is_past && x.is_start()) {
+        problems.push(format!(
+            "interval starting at {} was never closed",
+            last.time
+        ));
+    }
+    problems
+}
 
-        log::info!("read {log:#?}");
+/// accumulated over every day that `show` displayed
+#[derive(Default)]
+struct Total {
+    /// sum of all completed intervals
+    duration: Duration,
+    /// number of days whose last interval was still open and therefore not counted
+    open: usize,
+    /// number of days that were displayed
+    days: usize,
+    /// number of intervals that were hidden by `ShowOptions::min_duration`
+    filtered: usize,
+}
 
-        let now = chrono::offset::Local::now();
-        let date = now.date_naive();
-        let time = now.time();
-        Ok(Self {
-            log,
-            date: Date(date),
-            time: Time(time),
+impl Total {
+    fn add(&mut self, other: &Total) {
+        self.duration += other.duration;
+        self.open += other.open;
+        self.days += other.days;
+        self.filtered += other.filtered;
+    }
+}
+
+/// a day as printed by `show --json`
+#[derive(Serialize)]
+struct JsonDay {
+    project: String,
+    date: Date,
+    duration_seconds: i64,
+    intervals: Vec<Item>,
+    open: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    events: Vec<Event>,
+}
+
+/// the gaps between consecutive intervals, up to the start of the open one if there is any
+fn breaks(items: &[Item], open: Option<Time>) -> Vec<Item> {
+    let mut starts: Vec<_> = items.iter().skip(1).map(|item| item.start).collect();
+    starts.extend(open);
+    items
+        .iter()
+        .zip(starts)
+        .filter(|(item, start)| item.end < *start)
+        .map(|(item, start)| Item {
+            start: item.end,
+            end: start,
+            note: None,
+            correction: Duration::zero(),
         })
+        .collect()
+}
+
+fn show<F>(log: &Log, project: &str, options: &ShowOptions, mut display: F) -> anyhow::Result<Total>
+where
+    F: FnMut(&Date, Vec<Item>, Option<Time>, Duration) -> anyhow::Result<()>,
+{
+    if log.projects.is_empty() {
+        eprintln!("no data recorded");
+        return Ok(Total::default());
     }
 
-    fn insert(&mut self, project: String) {
-        let entry = self
-            .log
+    // with --depth, `project` is a group and the intervals of all of its members are combined
+    let members: Vec<_> = match options.depth {
+        Some(depth) => log
             .projects
-            .entry(project)
-            .or_default()
-            .entries
-            .entry(self.date)
-            .or_default();
+            .iter()
+            .filter(|(name, _)| project_group(name, depth) == project)
+            .map(|(_, info)| info)
+            .collect(),
+        None => log.projects.get(project).into_iter().collect(),
+    };
+    if members.is_empty() {
+        return Err(anyhow!(
+            "project {} is not present in log file",
+            display_project(project)
+        ));
+    }
 
-        if let Some(last_timestamp) = entry.last_mut() {
-            let dur = Duration::seconds(last_timestamp.tolerance as i64);
-            let now = NaiveDateTime::new(self.date.0, self.time.0);
-            let last_acceptable = NaiveDateTime::new(self.date.0, last_timestamp.time.0) + dur;
-            if last_timestamp.is_end() && now <= last_acceptable {
-                last_timestamp.time = self.time;
-                return;
+    let mut days: BTreeMap<Date, Vec<&[TimeStamp]>> = BTreeMap::new();
+    for info in &members {
+        for (date, day) in info.entries.iter() {
+            if options.range.contains(date) {
+                days.entry(*date).or_default().push(day);
             }
         }
+    }
+    // days with events are visited even without intervals, so their events can be shown
+    let mut marked = BTreeSet::new();
+    for event in members
+        .iter()
+        .filter(|_| options.events)
+        .flat_map(|info| &info.events)
+    {
+        if options.range.contains(&event.date) {
+            marked.insert(event.date);
+            days.entry(event.date).or_default();
+        }
+    }
+    // an open end of the range is bounded by the first or last recorded day
+    let first = options.range.from.or(days.keys().next().copied());
+    let last = options.range.to.or(days.keys().next_back().copied());
+    if let (true, Some(first), Some(last)) = (options.show_empty, first, last) {
+        for date in first.0.iter_days().take_while(|date| *date <= last.0) {
+            let weekend = matches!(date.weekday(), Weekday::Sat | Weekday::Sun);
+            if !(weekend && options.skip_weekends) {
+                days.entry(Date(date)).or_default();
+            }
+        }
+    }
+    let skip = options
+        .tail
+        .map_or(0, |tail| days.len().saturating_sub(tail));
 
-        let typ = if entry.last().is_some_and(|l| l.is_start()) {
-            TimeStampType::End
-        } else {
-            TimeStampType::Start
-        };
-
-        entry.push(TimeStamp {
-            typ,
-            time: self.time,
-            tolerance: 60 * 15,
-        });
+    let mut days: Vec<_> = days.into_iter().skip(skip).collect();
+    if options.reverse {
+        days.reverse();
     }
 
-    fn commit(&self, output: impl Write) -> anyhow::Result<()> {
-        serde_json::to_writer_pretty(output, &self.log)?;
-        Ok(())
+    let mut total = Total::default();
+    for (date, day) in days {
+        if !options.show_empty
+            && !marked.contains(&date)
+            && !day.iter().flat_map(|day| day.iter()).any(|x| x.is_start())
+        {
+            log::warn!(
+                "day {date} is present in {} but was empty",
+                display_project(project)
+            );
+            continue;
+        }
+        let mut times = (vec![], None);
+        for day in day {
+            let (items, open) = intervals_for_day(day);
+            times.0.extend(items);
+            times.1 = times.1.max(open);
+        }
+        times.0.sort_by_key(|item| item.start);
+        for pair in times.0.windows(2) {
+            if pair[1].start < pair[0].end {
+                log::warn!(
+                    "intervals {} - {} and {} - {} on {date} in {} overlap and are both counted",
+                    pair[0].start,
+                    pair[0].end,
+                    pair[1].start,
+                    pair[1].end,
+                    display_project(project)
+                );
+            }
+        }
+        if let Some(minutes) = options.snap {
+            times.0.iter_mut().for_each(|item| options.snap_item(item));
+            for pair in times.0.windows(2) {
+                if pair[1].start < pair[0].end {
+                    log::warn!(
+                        "intervals {} - {} and {} - {} on {date} in {} overlap after snapping to \
+                         {minutes}min",
+                        pair[0].start,
+                        pair[0].end,
+                        pair[1].start,
+                        pair[1].end,
+                        display_project(project)
+                    );
+                }
+            }
+        }
+        times.0 = options.merge_gaps(times.0);
+        total.filtered += options.filter_items(&mut times.0);
+        if options.invert {
+            times = (breaks(&times.0, times.1), None);
+        }
+        let duration = options.day_duration(&times.0);
+
+        total.duration += duration;
+        if times.1.is_some() {
+            total.open += 1;
+        }
+        total.days += 1;
+        display(&date, times.0, times.1, duration)?;
     }
+
+    Ok(total)
 }
 
-struct Item {
-    start: Time,
-    end: Time,
+/// the duration of the interval of `project` from `start` to the time of `recorder`, taken from its
+/// timestamps today or yesterday so a change of the UTC offset in between is counted
+fn session(recorder: &Record, project: &str, start: Time) -> Duration {
+    let info = recorder.log.projects.get(project);
+    let yesterday = recorder.date.0.pred_opt().map(Date);
+    [Some(recorder.date), yesterday]
+        .into_iter()
+        .flatten()
+        .filter_map(|date| info?.entries.get(&date))
+        .flat_map(|day| intervals_for_day(day).0)
+        .find(|item| item.start == start && item.end == recorder.time)
+        .unwrap_or(Item {
+            start,
+            end: recorder.time,
+            note: None,
+            correction: Duration::zero(),
+        })
+        .duration()
 }
 
-impl Item {
-    fn duration(&self) -> i64 {
-        let delta = self.end.0 - self.start.0;
-        delta.num_seconds()
+/// print whether `project` is clocked in at the time of `recorder`
+fn status(recorder: &Record, project: &str) {
+    if let Some((_, start)) = recorder.open_start(project) {
+        // may have started yesterday, which the duration of an item accounts for
+        let elapsed = Item {
+            start,
+            end: recorder.time,
+            note: None,
+            correction: Duration::zero(),
+        }
+        .duration();
+        println!("clocked in since {start} (elapsed {})", MyDuration(elapsed));
+        return;
+    }
+
+    let active = recorder
+        .log
+        .projects
+        .get(project)
+        .and_then(|project| project.entries.get(&recorder.date))
+        .is_some_and(|entry| !entry.is_empty());
+    match active {
+        true => println!("not clocked in"),
+        false => println!("no activity today"),
     }
 }
 
-struct MyDuration(Duration);
+/// the project used when `--project` is not given, stored under the empty key
+const DEFAULT_PROJECT: &str = "";
 
-impl Display for MyDuration {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.0.num_hours() != 0 {
-            write!(f, "{}h", self.0.num_hours())?;
-        }
-        write!(f, "{}min", self.0.num_minutes() % 60)?;
-        Ok(())
+/// name that can be passed to `--project` to explicitly refer to the default project
+const DEFAULT_PROJECT_ALIAS: &str = "default";
+
+/// the first `depth` levels of a `/` separated project name, e.g. `client` for `client/frontend`
+fn project_group(project: &str, depth: usize) -> &str {
+    match project.match_indices('/').nth(depth.saturating_sub(1)) {
+        Some((i, _)) => &project[..i],
+        None => project,
     }
 }
 
-struct DecimalDuration(Duration);
+/// every project of the log, or every group if `depth` is given
+fn project_groups(log: &Log, depth: Option<usize>) -> Vec<String> {
+    let mut groups: Vec<String> = log
+        .projects
+        .keys()
+        .map(|name| match depth {
+            Some(depth) => project_group(name, depth).to_string(),
+            None => name.clone(),
+        })
+        .collect();
+    groups.sort();
+    groups.dedup();
+    groups
+}
 
-impl Display for DecimalDuration {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:.02}", self.0.num_minutes() as f64 / 60.0)?;
-        Ok(())
+/// the key under which a project given on the command line is stored
+fn project_key(project: Option<String>) -> String {
+    match project {
+        Some(project) if project != DEFAULT_PROJECT_ALIAS => project,
+        _ => DEFAULT_PROJECT.to_string(),
     }
 }
 
-fn show<F>(input: impl Read, project: &str, display: F) -> anyhow::Result<()>
-where
-    F: Fn(&Date, Vec<Item>, Option<Time>) -> anyhow::Result<()>,
-{
-    fn get_times<'a>(
-        mut iter: impl Iterator<Item = &'a TimeStamp>,
-        mut start: Time,
-    ) -> (Vec<Item>, Option<Time>) {
-        let mut items = vec![];
-        while let Some(head) = iter.next() {
-            if head.is_start() {
-                start = head.time;
-            } else {
-                items.push(Item {
-                    start,
-                    end: head.time,
-                });
-                let Some(next) = iter.find(|x| x.is_start()) else {
-                    return (items, None);
-                };
-                start = next.time;
-            }
+/// the log file to use: `--file` or `--profile`, then `$RECORD_HOURS_FILE`, then the config
+/// file, then an existing `hours.log.json`
+fn log_path(
+    file: Option<PathBuf>,
+    profile: Option<String>,
+    config: &Config,
+) -> anyhow::Result<PathBuf> {
+    let path = match profile {
+        Some(profile) => config.profile(&profile)?,
+        None => file
+            .or_else(|| std::env::var_os("RECORD_HOURS_FILE").map(PathBuf::from))
+            .or_else(|| config.log_file.clone())
+            .unwrap_or_else(default_log_path),
+    };
+    log::info!("using the log {}", path.display());
+    Ok(path)
+}
+
+/// the first existing hours.log.json in the working directory, `$XDG_DATA_HOME/record-hours` or
+/// the home directory, so the same log is found from anywhere. A new log is started in the
+/// working directory
+fn default_log_path() -> PathBuf {
+    const NAME: &str = "hours.log.json";
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".local").join("share")));
+
+    let mut candidates = vec![PathBuf::from(NAME)];
+    candidates.extend(data_home.map(|dir| dir.join("record-hours").join(NAME)));
+    candidates.extend(home.map(|home| home.join(NAME)));
+    candidates
+        .into_iter()
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(NAME))
+}
+
+/// point to where parsing `path` failed, as `path:line:column: message`
+fn parse_error(path: &Path, e: anyhow::Error) -> anyhow::Error {
+    match e.downcast_ref::<serde_json::Error>() {
+        Some(json) if json.line() != 0 => {
+            let message = json.to_string();
+            let position = format!(" at line {} column {}", json.line(), json.column());
+            let message = message.strip_suffix(&position).unwrap_or(&message);
+            anyhow!(
+                "{}:{}:{}: {message}",
+                path.display(),
+                json.line(),
+                json.column()
+            )
         }
-        (items, Some(start))
+        _ => e,
     }
+}
 
-    let stored: Log = serde_json::from_reader(input).context("input file was missing")?;
+/// read the log, with every timestamp converted into the local timezone
+fn read_log(path: &Path) -> anyhow::Result<Log> {
+    let mut log = if is_stdin(path) {
+        Log::read(std::io::stdin().lock(), Format::Json)
+    } else {
+        Log::read(File::open(path)?, Format::of(path))
+    }
+    .map_err(|e| parse_error(path, e))?;
+    log.localize(&Local);
+    Ok(log)
+}
 
-    let project_info = stored
-        .projects
-        .get(project)
-        .ok_or(anyhow!("project {project} is not present in log file"))?;
+/// let the user edit the log at `path` until it parses, returns the edited log or `None` if the
+/// user gave up
+fn edit_log(path: &Path, tmp: &Path, clock: &Now) -> anyhow::Result<Option<Record>> {
+    // a compressed log is edited decompressed, it is compressed again when saved
+    let original = if path.exists() {
+        decompress(std::fs::read(path)?)?
+    } else if Format::of(path) == Format::Toml {
+        vec![]
+    } else {
+        b"{}".to_vec()
+    };
+    std::fs::write(tmp, original)?;
 
-    for (date, day) in project_info.entries.iter() {
-        let mut iter = day.iter();
-        let Some(start) = iter.find(|x| x.is_start()) else {
-            log::warn!("day {date} is present in {project} but was empty");
-            continue;
-        };
-        let times = get_times(iter, start.time);
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or(anyhow!("$EDITOR is empty"))?;
+    loop {
+        let status = std::process::Command::new(program)
+            .args(words.clone())
+            .arg(tmp)
+            .status()
+            .with_context(|| format!("failed to run {editor}"))?;
+        if !status.success() {
+            return Err(anyhow!("{editor} exited with {status}"));
+        }
 
-        display(date, times.0, times.1)?;
+        let record = Record::open(File::open(tmp)?, Format::of(path), clock)
+            .map_err(|e| parse_error(path, e));
+        match record {
+            Ok(record) => return Ok(Some(record)),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                eprint!("edit again? [Y/n] ");
+                let mut answer = String::new();
+                let read = std::io::stdin().read_line(&mut answer)?;
+                if read == 0 || answer.trim().eq_ignore_ascii_case("n") {
+                    return Ok(None);
+                }
+            }
+        }
     }
+}
 
-    Ok(())
+/// ask on the terminal whether to go ahead with `prompt`, the default is no.
+///
+/// Goes ahead without asking if `force` is given or stdin is not a terminal, so scripts are not
+/// blocked. A refusal is reported as leaving the log unchanged
+fn confirm(prompt: &str, force: bool) -> bool {
+    if force || !std::io::stdin().is_terminal() {
+        return true;
+    }
+    eprint!("{prompt} [y/N] ");
+    let mut answer = String::new();
+    let yes = match std::io::stdin().read_line(&mut answer) {
+        Ok(read) => read != 0 && matches!(answer.trim(), "y" | "Y" | "yes"),
+        Err(_) => false,
+    };
+    if !yes {
+        eprintln!("nothing was changed");
+    }
+    yes
+}
+
+/// open the log for recording. Unlike `read_log`, the timestamps are left in the offset they were
+/// recorded in, so saving the log doesn't convert them. New timestamps get the default tolerance
+/// of `config`
+fn open_record(path: &Path, clock: &Now, config: &Config) -> anyhow::Result<Record> {
+    let mut record = if is_stdin(path) {
+        Record::open(std::io::stdin().lock(), Format::Json, clock)
+    } else if !path.exists() {
+        // expected on first use, unlike an empty file which gets a warning
+        log::info!("{} does not exist yet, starting a new log", path.display());
+        Record::open(&b"{}"[..], Format::Json, clock)
+    } else {
+        let infile = File::open(path)?;
+        Record::open(infile, Format::of(path), clock)
+    }
+    .map_err(|e| parse_error(path, e))?;
+    if let Some(tolerance) = config.default_tolerance_seconds {
+        record.tolerance = tolerance;
+    }
+    Ok(record)
 }
 
 fn main() -> anyhow::Result<()> {
     let app = App::parse();
-    env_logger::init();
+    let level = if app.quiet {
+        LevelFilter::Off
+    } else {
+        match app.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .init();
+    let config = Config::load()?;
+    let path = log_path(app.file, app.profile, &config)?;
+    let clock = Now(app.now);
 
     match app.command {
-        Commands::Record { auto: _, project } => {
-            let project = project.unwrap_or_default();
-            let path = app.file.unwrap_or_else(|| PathBuf::from("hours.log.json"));
+        Commands::Record {
+            auto: _,
+            project,
+            tolerance,
+            at,
+            date,
+            note,
+            no_merge,
+            dry_run,
+            force,
+        } => {
+            let project = config.project(project);
 
-            let mut recorder = if !path.exists() {
-                Record::open(std::io::empty())?
-            } else {
-                let infile = File::open(&path)?;
-                Record::open(infile)?
-            };
+            let mut recorder = open_record(&path, &clock, &config)?;
+            if let Some(at) = at {
+                recorder.time = at;
+            }
+            if let Some(date) = date {
+                recorder.date = date;
+            }
+            if at.is_some() || date.is_some() {
+                // a backfilled time is in the offset the local timezone had back then
+                let when = NaiveDateTime::new(recorder.date.0, recorder.time.0);
+                if let Some(when) = Local.from_local_datetime(&when).earliest() {
+                    recorder.offset = when.offset().fix();
+                }
+            }
+
+            let insertion = recorder.insert(project.clone(), tolerance, note, !no_merge, force)?;
+
+            if dry_run {
+                let name = display_project(&project);
+                let now = recorder.time;
+                match insertion {
+                    Insertion::Started => println!("would start project {name} at {now}"),
+                    Insertion::Stopped { start } => {
+                        println!("would stop project {name} at {now}, session started at {start}");
+                    }
+                    Insertion::Extended { previous, .. } => {
+                        println!("would merge into the interval of {name} ending {previous}");
+                    }
+                }
+                return Ok(());
+            }
+            recorder.save(&path, config.backup_count)?;
+        }
+        Commands::Toggle { project } => {
+            let project = config.project(project);
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            let now = recorder.time;
+            let insertion = recorder.insert(project.clone(), None, None, true, false)?;
+            recorder.save(&path, config.backup_count)?;
+
+            let name = display_project(&project);
+            let session = |start| MyDuration(session(&recorder, &project, start));
+            match insertion {
+                Insertion::Started => println!("started project {name} at {now}"),
+                Insertion::Stopped { start } => {
+                    println!(
+                        "stopped project {name} at {now}, session {}",
+                        session(start)
+                    );
+                }
+                Insertion::Extended { start, previous } => {
+                    // the stop at `previous` was recent enough to be moved instead
+                    print!("stopped project {name} at {now} (moved from {previous})");
+                    match start {
+                        Some(start) => println!(", session {}", session(start)),
+                        None => println!(),
+                    }
+                }
+            }
+        }
+        Commands::Continue => {
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            let (project, open) = recorder
+                .log
+                .last_project()
+                .ok_or(anyhow!("no previous project to continue"))?;
+            let project = project.to_string();
+            let name = display_project(&project);
+            if open {
+                return Err(anyhow!("project {name} is still clocked in"));
+            }
+
+            let now = recorder.time;
+            let insertion = recorder.insert(project.clone(), None, None, true, false)?;
+            recorder.save(&path, config.backup_count)?;
+
+            match insertion {
+                Insertion::Extended { previous, .. } => {
+                    println!("continued project {name} at {now} (moved from {previous})")
+                }
+                _ => println!("continued project {name} at {now}"),
+            }
+        }
+        Commands::Add {
+            project,
+            duration,
+            date,
+            note,
+        } => {
+            let project = config.project(project);
+            let mut recorder = open_record(&path, &clock, &config)?;
+            if let Some(date) = date {
+                recorder.date = date;
+            }
+
+            let item = recorder.add(project.clone(), duration, None, note)?;
+            recorder.save(&path, config.backup_count)?;
+            println!(
+                "added {} - {} to {} on {}",
+                item.start,
+                item.end,
+                display_project(&project),
+                recorder.date
+            );
+        }
+        Commands::Mark { project, label } => {
+            let project = config.project(project);
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            let now = recorder.time;
+            let name = display_project(&project);
+            recorder.mark(project.clone(), label.clone());
+            recorder.save(&path, config.backup_count)?;
+            println!("marked {label:?} in {name} at {now}");
+        }
+        Commands::In { project, at, force } => {
+            let project = config.project(project);
+            let mut recorder = open_record(&path, &clock, &config)?;
+            if let Some(at) = at {
+                recorder.time = at;
+            }
+
+            let now = recorder.time;
+            let name = display_project(&project);
+            let closed = recorder
+                .clock_in(project.clone(), None, None, force)
+                .with_context(|| format!("cannot clock in to {name}"))?;
+            recorder.save(&path, config.backup_count)?;
+
+            if let Some(start) = closed {
+                println!("stopped the interval of {name} that started at {start}");
+            }
+            println!("started project {name} at {now}");
+        }
+        Commands::Out { project, at } => {
+            let project = config.project(project);
+            let mut recorder = open_record(&path, &clock, &config)?;
+            if let Some(at) = at {
+                recorder.time = at;
+            }
 
-            recorder.insert(project);
+            let now = recorder.time;
+            let name = display_project(&project);
+            let start = recorder
+                .clock_out(project.clone(), None, None)
+                .with_context(|| format!("cannot clock out of {name}"))?;
+            recorder.save(&path, config.backup_count)?;
+
+            println!(
+                "stopped project {name} at {now}, session {}",
+                MyDuration(session(&recorder, &project, start))
+            );
+        }
+        Commands::Stop { project } => {
+            let project = config.project(project);
 
-            let outfile = File::create(&path)?;
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            recorder.stop(project)?;
+
+            recorder.save(&path, config.backup_count)?;
+        }
+        Commands::Status { project } => {
+            let project = config.project(project);
 
-            // recorder.commit(std::io::stdout().lock())?;
-            recorder.commit(outfile)?;
+            let recorder = open_record(&path, &clock, &config)?;
+            status(&recorder, &project);
         }
-        Commands::Show {
+        Commands::Show(args) => {
+            show::run_file(&args, &path, &config, &clock)?;
+        }
+        Commands::Summary {
             project,
+            by,
+            target,
+        } => {
+            let project = config.project(project);
+            let target = config.target(target)?;
+            let log = read_log(&path)?;
+
+            let mut periods: BTreeMap<String, (Duration, i32)> = BTreeMap::new();
+            let total = show(
+                &log,
+                &project,
+                &ShowOptions::default(),
+                |date, _, _, duration| {
+                    let period = periods
+                        .entry(by.label(date))
+                        .or_insert((Duration::zero(), 0));
+                    period.0 += duration;
+                    period.1 += 1;
+                    Ok(())
+                },
+            )?;
+
+            for (period, (duration, days)) in periods {
+                match target {
+                    Some(target) => {
+                        let (target, diff) = target
+                            .checked_mul(days)
+                            .and_then(|target| Some((target, duration.checked_sub(&target)?)))
+                            .ok_or(anyhow!("the target of {period} is too long"))?;
+                        let sign = if diff < Duration::zero() { "-" } else { "+" };
+                        println!(
+                            "{period}: {} / {}, {sign}{}",
+                            MyDuration(duration),
+                            MyDuration(target),
+                            MyDuration(diff.abs())
+                        );
+                    }
+                    None => println!("{period}: {}", MyDuration(duration)),
+                }
+            }
+            // only days with entries count, days without any don't lower the average
+            if total.days != 0 {
+                let average = total.duration / total.days as i32;
+                println!("avg/day: {}", MyDuration(average));
+            }
+        }
+        Commands::Total {
+            project,
+            from,
+            to,
             decimal,
-            format,
         } => {
-            if project.len() > 1 {
-                warn!("specifying multiple projects isn't implemented atm")
-            }
-            let project = project.first().cloned().unwrap_or_default();
-            let path = app.file.unwrap_or_else(|| PathBuf::from("hours.log.json"));
-            let infile = File::open(path)?;
-
-            if let Some(format) = &format {
-                show(infile, &project, |&date, times, _| {
-                    let duration: Duration = times.iter().map(|x| x.end.0 - x.start.0).sum();
-                    let fmt = format::Formatter {
-                        date,
-                        duration,
-                        format,
-                        project: &project,
-                    };
-                    println!("{fmt}");
+            let project = config.project(project);
+            let log = read_log(&path)?;
+            let options = ShowOptions {
+                range: DateRange::new(from, to)?,
+                ..ShowOptions::default()
+            };
+            let total = show(&log, &project, &options, |_, _, _, _| Ok(()))?;
+            if decimal {
+                println!("{}", DecimalDuration::new(total.duration));
+            } else {
+                println!("{}", total.duration.num_minutes());
+            }
+        }
+        Commands::Stats { project, from, to } => {
+            let project = config.project(project);
+            let log = read_log(&path)?;
+            let options = ShowOptions {
+                range: DateRange::new(from, to)?,
+                ..ShowOptions::default()
+            };
+            let mut sessions = vec![];
+            show(&log, &project, &options, |_, items, _, _| {
+                sessions.extend(items.iter().map(Item::duration));
+                Ok(())
+            })?;
+            sessions.sort();
+
+            let name = display_project(&project);
+            let (Some(&shortest), Some(&longest)) = (sessions.first(), sessions.last()) else {
+                println!("no completed sessions of {name}");
+                return Ok(());
+            };
+            let count = sessions.len();
+            let mean = sessions.iter().sum::<Duration>() / count as i32;
+            let median = match count % 2 {
+                0 => (sessions[count / 2 - 1] + sessions[count / 2]) / 2,
+                _ => sessions[count / 2],
+            };
+            println!("sessions: {count}");
+            println!("longest: {}", MyDuration(longest));
+            println!("shortest: {}", MyDuration(shortest));
+            println!("mean: {}", MyDuration(mean));
+            println!("median: {}", MyDuration(median));
+        }
+        Commands::Totals {
+            from,
+            since,
+            to,
+            decimal,
+            depth,
+        } => {
+            let log = read_log(&path)?;
+            let options = ShowOptions {
+                range: DateRange::new(
+                    from.or(since
+                        .map(|since| since.start(clock.now().date_naive()))
+                        .transpose()?),
+                    to,
+                )?,
+                depth: depth.map(|depth| depth as usize),
+                ..ShowOptions::default()
+            };
+
+            let mut totals = vec![];
+            let mut days = BTreeSet::new();
+            for project in project_groups(&log, options.depth) {
+                let total = show(&log, &project, &options, |&date, _, _, _| {
+                    days.insert(date);
                     Ok(())
                 })?;
-                return Ok(());
+                if total.days != 0 {
+                    totals.push((project, total.duration));
+                }
             }
-            show(infile, &project, |date, times, last| {
-                let mut f = std::io::stdout().lock();
-                let duration: Duration = times.iter().map(|x| x.end.0 - x.start.0).sum();
+            totals.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
 
-                let duration: Box<dyn Display> = if decimal {
-                    Box::new(DecimalDuration(duration))
-                } else {
-                    Box::new(MyDuration(duration))
+            let duration_format = config.duration_format()?;
+            let duration = |duration| -> Box<dyn Display> {
+                match (decimal, duration_format) {
+                    (true, _) => Box::new(DecimalDuration::new(duration)),
+                    (false, Some(format)) => {
+                        Box::new(format::DurationFormatter { duration, format })
+                    }
+                    (false, None) => Box::new(MyDuration(duration)),
+                }
+            };
+            let grand_total: Duration = totals.iter().map(|(_, total)| *total).sum();
+            for (name, total) in totals {
+                let share = match grand_total.num_seconds() {
+                    0 => 0.0,
+                    seconds => total.num_seconds() as f64 / seconds as f64 * 100.0,
                 };
-                writeln!(f, "{date} ({}):", duration)?;
-                for Item { start, end } in times {
-                    writeln!(f, "  - {start} - {end}")?;
+                println!(
+                    "{}: {} ({share:.1}%)",
+                    display_project(&name),
+                    duration(total)
+                );
+            }
+            println!("Grand total: {}", duration(grand_total));
+            // only days with entries in any of the projects count
+            if !days.is_empty() {
+                println!("avg/day: {}", duration(grand_total / days.len() as i32));
+            }
+
+            // counted by project even with --depth, the members of a group may be classified
+            // differently
+            if log.projects.values().any(|info| info.billable.is_some()) {
+                let options = ShowOptions {
+                    depth: None,
+                    ..options
+                };
+                let (mut billable, mut non_billable) = (Duration::zero(), Duration::zero());
+                for (name, info) in &log.projects {
+                    let total = show(&log, name, &options, |_, _, _, _| Ok(()))?;
+                    match info.billable {
+                        Some(true) => billable += total.duration,
+                        Some(false) => non_billable += total.duration,
+                        None => {}
+                    }
                 }
-                if let Some(start) = last {
-                    writeln!(f, "  - {start} - ")?;
+                println!("Billable: {}", duration(billable));
+                println!("Non-billable: {}", duration(non_billable));
+                let unclassified = grand_total - billable - non_billable;
+                if unclassified != Duration::zero() {
+                    println!("Unclassified: {}", duration(unclassified));
                 }
-                Ok(())
-            })?;
+            }
+        }
+        Commands::Matrix { from, to } => {
+            let log = read_log(&path)?;
+            let options = ShowOptions {
+                range: DateRange::new(from, to)?,
+                ..ShowOptions::default()
+            };
+            let projects: Vec<&str> = log.projects.keys().map(String::as_str).collect();
+            let mut weeks: BTreeMap<String, Vec<Duration>> = BTreeMap::new();
+            for (i, project) in projects.iter().enumerate() {
+                show(&log, project, &options, |date, _, _, duration| {
+                    let week = weeks
+                        .entry(Period::Week.label(date))
+                        .or_insert_with(|| vec![Duration::zero(); projects.len()]);
+                    week[i] += duration;
+                    Ok(())
+                })?;
+            }
+            export::matrix(std::io::stdout().lock(), &projects, &weeks)?;
+        }
+        Commands::Check { project } => {
+            let project = project.map(|project| project_key(Some(project)));
+            let recorder = open_record(&path, &clock, &config)?;
+
+            let mut count = 0;
+            for (name, info) in &recorder.log.projects {
+                if project.as_ref().is_some_and(|project| project != name) {
+                    continue;
+                }
+                let name = display_project(name);
+                for (date, day) in &info.entries {
+                    for problem in check_day(day, *date < recorder.date) {
+                        println!("{name} {date}: {problem}");
+                        count += 1;
+                    }
+                }
+            }
+
+            if count != 0 {
+                return Err(anyhow!("found {count} problem(s)"));
+            }
+            println!("no problems found");
+        }
+        Commands::List { sort } => {
+            let log = open_record(&path, &clock, &config)?.log;
+
+            let mut projects: Vec<_> = log
+                .projects
+                .iter()
+                .map(|(name, project)| (name, project.entries.len(), project.total()))
+                .collect();
+            // sorting is stable and the projects come sorted by name, which breaks ties
+            match sort {
+                ListSort::Name => {}
+                ListSort::Days => projects.sort_by_key(|(_, days, _)| std::cmp::Reverse(*days)),
+                ListSort::Hours => projects.sort_by_key(|(_, _, total)| std::cmp::Reverse(*total)),
+            }
+
+            for (name, days, total) in projects {
+                let name = display_project(name);
+                println!("{name}: {days} days, {}", MyDuration(total));
+            }
+        }
+        Commands::Delete { project, yes } => {
+            let project = project_key(Some(project));
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            let removed = recorder.delete(&project)?;
+            let timestamps: usize = removed.entries.values().map(Vec::len).sum();
+            let name = display_project(&project);
+            println!(
+                "project {name}: {} days, {timestamps} timestamps",
+                removed.entries.len()
+            );
+            if !confirm(&format!("delete project {name}?"), yes) {
+                return Ok(());
+            }
+
+            recorder.save(&path, config.backup_count)?;
+        }
+        Commands::Tag {
+            project,
+            billable,
+            non_billable: _,
+        } => {
+            let project = config.project(project);
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            let name = display_project(&project);
+            recorder
+                .log
+                .projects
+                .get_mut(&project)
+                .ok_or(anyhow!("project {name} is not present in log file"))?
+                .billable = Some(billable);
+            recorder.save(&path, config.backup_count)?;
+
+            let class = if billable { "billable" } else { "non-billable" };
+            println!("marked project {name} as {class}");
+        }
+        Commands::Rename { from, to, yes } => {
+            let (from, to) = (project_key(Some(from)), project_key(Some(to)));
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            let merge = recorder.log.projects.contains_key(&to);
+            recorder.rename(&from, to.clone())?;
+            let prompt = format!(
+                "rename {} to {}{}?",
+                display_project(&from),
+                display_project(&to),
+                if merge {
+                    ", merging it into the existing project"
+                } else {
+                    ""
+                }
+            );
+            if !confirm(&prompt, yes) {
+                return Ok(());
+            }
+
+            recorder.save(&path, config.backup_count)?;
+        }
+        Commands::Move {
+            date,
+            from,
+            to,
+            yes,
+        } => {
+            let (from, to) = (project_key(Some(from)), project_key(Some(to)));
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            let moved = recorder.move_day(date, &from, to.clone())?;
+            let prompt = format!(
+                "move {moved} timestamps of {date} from {} to {}?",
+                display_project(&from),
+                display_project(&to)
+            );
+            if !confirm(&prompt, yes) {
+                return Ok(());
+            }
+            recorder.save(&path, config.backup_count)?;
+        }
+        Commands::Split { project, date, at } => {
+            let project = config.project(project);
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            let Item { start, end, .. } = recorder.split(&project, date, at)?;
+            println!("split {start} - {end} into {start} - {at} and {at} - {end}");
+
+            recorder.save(&path, config.backup_count)?;
+        }
+        Commands::Prune {
+            project,
+            min_duration,
+            dry_run,
+            yes,
+        } => {
+            let project = config.project(project);
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            let min = prune_minutes(min_duration)?;
+            let removed = recorder.prune(&project, min)?;
+            for (date, Item { start, end, .. }) in &removed {
+                println!("{date}: {start} - {end}");
+            }
+            println!(
+                "would remove {} interval(s) from {}",
+                removed.len(),
+                display_project(&project)
+            );
+
+            if !dry_run && !removed.is_empty() && confirm("remove them?", yes) {
+                recorder.save(&path, config.backup_count)?;
+                println!("removed {} interval(s)", removed.len());
+            }
+        }
+        Commands::Report { project, output } => {
+            let project = config.project(project);
+            let log = read_log(&path)?;
+
+            match output {
+                Some(output) => report::write(File::create(output)?, &log, &project)?,
+                None => report::write(std::io::stdout().lock(), &log, &project)?,
+            }
+        }
+        Commands::Edit => {
+            if is_stdin(&path) {
+                return Err(anyhow!(
+                    "the log can't be edited on stdin, use a file instead"
+                ));
+            }
+            let tmp = std::env::temp_dir().join(format!(
+                "record-hours-{}.{}",
+                std::process::id(),
+                Format::of(&path).extension()
+            ));
+
+            let result = edit_log(&path, &tmp, &clock);
+            let _ = std::fs::remove_file(&tmp);
+            match result? {
+                Some(recorder) => recorder.save(&path, config.backup_count)?,
+                None => println!("not saving, {} is unchanged", path.display()),
+            }
+        }
+        Commands::Import { from, yes } => {
+            let mut recorder = open_record(&path, &clock, &config)?;
+
+            let other = Log::read(File::open(&from)?, Format::of(&from))
+                .map_err(|e| parse_error(&from, e))?;
+            let (projects, days, timestamps) = recorder.import(other);
+            println!("adding {projects} projects, {days} days, {timestamps} timestamps");
+            if !confirm("import them?", yes) {
+                return Ok(());
+            }
+
+            recorder.save(&path, config.backup_count)?;
+        }
+        Commands::Completions { shell } => {
+            completions::generate(shell, &mut App::command(), std::io::stdout().lock())?;
+        }
+        Commands::Export { project, format } => {
+            let project = config.project(project);
+            let log = read_log(&path)?;
+
+            format.header(std::io::stdout().lock())?;
+            show(
+                &log,
+                &project,
+                &ShowOptions::default(),
+                |date, times, last, _| {
+                    format.day(std::io::stdout().lock(), &project, date, &times, last)
+                },
+            )?;
+            format.footer(std::io::stdout().lock())?;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_units() {
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::seconds(90));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("90").is_err());
+    }
+
+    #[test]
+    fn duration_too_long() {
+        assert!(parse_duration("99999999999999999999h").is_err());
+        assert!(parse_duration("9223372036854775807s").is_err());
+        assert!(parse_duration(&format!("{}h", "9".repeat(400))).is_err());
+    }
+
+    #[test]
+    fn snap() {
+        let time = |h, m| Time(NaiveTime::from_hms_opt(h, m, 0).unwrap());
+        let mut item = Item {
+            start: time(9, 7),
+            end: time(10, 52),
+            note: None,
+            correction: Duration::zero(),
+        };
+        let options = ShowOptions {
+            snap: Some(15),
+            ..Default::default()
+        };
+        options.snap_item(&mut item);
+        assert_eq!((item.start, item.end), (time(9, 0), time(11, 0)));
+
+        // too large to be a number of seconds, left alone instead of overflowing
+        let options = ShowOptions {
+            snap: Some(100_000_000),
+            ..Default::default()
+        };
+        options.snap_item(&mut item);
+        assert_eq!((item.start, item.end), (time(9, 0), time(11, 0)));
+
+        // an end snapped to midnight keeps the whole day
+        let snap = |minutes, start, end| {
+            let mut item = Item {
+                start,
+                end,
+                note: None,
+                correction: Duration::zero(),
+            };
+            let options = ShowOptions {
+                snap: Some(minutes),
+                ..Default::default()
+            };
+            options.snap_item(&mut item);
+            (item.start, item.end, item.duration())
+        };
+        assert_eq!(
+            snap(1440, time(9, 7), time(10, 52)),
+            (time(0, 0), time(0, 0), Duration::hours(24))
+        );
+        assert_eq!(
+            snap(720, time(11, 0), time(13, 0)),
+            (time(0, 0), time(0, 0), Duration::hours(24))
+        );
+        assert_eq!(
+            snap(60, time(23, 10), time(23, 40)),
+            (time(23, 0), time(0, 0), Duration::hours(1))
+        );
+        assert_eq!(
+            snap(60, time(22, 10), time(0, 30)),
+            (time(22, 0), time(1, 0), Duration::hours(3))
+        );
+    }
+
+    #[test]
+    fn snap_at_most_a_day() {
+        assert!(App::try_parse_from(["record-hours", "show", "--snap", "1440"]).is_ok());
+        assert!(App::try_parse_from(["record-hours", "show", "--snap", "1441"]).is_err());
+        assert!(App::try_parse_from(["record-hours", "show", "--snap", "100000000"]).is_err());
+    }
+
+    #[test]
+    fn prune_min_duration() {
+        assert_eq!(prune_minutes(90).unwrap(), Duration::minutes(90));
+        assert!(prune_minutes(u64::MAX).is_err());
+        assert!(prune_minutes(i64::MAX as u64).is_err());
+    }
+
+    #[test]
+    fn show_visits_days_with_only_events() {
+        let log: Log = serde_json::from_str(
+            r#"{"work": {"events": [{"date": "2024-03-02", "time": "10:00:00", "label": "deploy"}],
+                "entries": {"2024-03-01": [
+                    {"type": "Start", "time": "09:00:00", "tolerance": 900},
+                    {"type": "End", "time": "11:00:00", "tolerance": 900}]}}}"#,
+        )
+        .unwrap();
+        let visit = |options: &ShowOptions| {
+            let mut dates = vec![];
+            show(&log, "work", options, |date, _, _, _| {
+                dates.push(date.to_string());
+                Ok(())
+            })
+            .unwrap();
+            dates
+        };
+        assert_eq!(visit(&ShowOptions::default()), ["2024-03-01"]);
+        let options = ShowOptions {
+            events: true,
+            ..Default::default()
+        };
+        assert_eq!(visit(&options), ["2024-03-01", "2024-03-02"]);
+    }
+
+    #[test]
+    fn since_units() {
+        assert!(matches!(parse_since("7d").unwrap(), Since::Days(7)));
+        assert!(matches!(parse_since("2w").unwrap(), Since::Days(14)));
+        assert!(matches!(parse_since("1m").unwrap(), Since::Months(1)));
+        assert!(matches!(parse_since("week").unwrap(), Since::Week));
+    }
+
+    #[test]
+    fn since_invalid() {
+        assert!(parse_since("").is_err());
+        assert!(parse_since("d").is_err());
+        assert!(parse_since("7y").is_err());
+        assert!(parse_since("7é").is_err());
+        assert!(parse_since("é").is_err());
+        assert!(parse_since("7dé").is_err());
+    }
+}
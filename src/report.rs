@@ -0,0 +1,77 @@
+use std::io::Write;
+
+use record_hours::{Log, MyDuration};
+
+use crate::{display_project, show, ShowOptions};
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}\
+table{border-collapse:collapse}\
+th,td{border:1px solid #ccc;padding:.3em .8em;text-align:left}\
+th{background:#f0f0f0}\
+tfoot td{font-weight:bold}";
+
+/// write a self-contained html timesheet of `project`
+pub fn write(mut f: impl Write, log: &Log, project: &str) -> anyhow::Result<()> {
+    let title = escape(&format!("Timesheet {}", display_project(project)));
+    writeln!(f, "<!DOCTYPE html>")?;
+    writeln!(f, "<html>")?;
+    writeln!(f, "<head>")?;
+    writeln!(f, "<meta charset=\"utf-8\">")?;
+    writeln!(f, "<title>{title}</title>")?;
+    writeln!(f, "<style>{STYLE}</style>")?;
+    writeln!(f, "</head>")?;
+    writeln!(f, "<body>")?;
+    writeln!(f, "<h1>{title}</h1>")?;
+    writeln!(f, "<table>")?;
+    writeln!(
+        f,
+        "<thead><tr><th>Date</th><th>Intervals</th><th>Duration</th></tr></thead>"
+    )?;
+    writeln!(f, "<tbody>")?;
+
+    let total = show(
+        log,
+        project,
+        &ShowOptions::default(),
+        |date, times, last, duration| {
+            let intervals = times
+                .iter()
+                .map(|item| format!("{} – {}", item.start, item.end))
+                .chain(last.map(|start| format!("{start} –")))
+                .collect::<Vec<_>>()
+                .join("<br>");
+            writeln!(
+                f,
+                "<tr><td>{date}</td><td>{intervals}</td><td>{}</td></tr>",
+                MyDuration(duration)
+            )?;
+            Ok(())
+        },
+    )?;
+
+    writeln!(f, "</tbody>")?;
+    writeln!(
+        f,
+        "<tfoot><tr><td colspan=\"2\">Total</td><td>{}</td></tr></tfoot>",
+        MyDuration(total.duration)
+    )?;
+    writeln!(f, "</table>")?;
+    writeln!(f, "</body>")?;
+    writeln!(f, "</html>")?;
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
@@ -0,0 +1,611 @@
+use std::{
+    fmt::Display,
+    io::{IsTerminal, StdoutLock, Write},
+    path::Path,
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, IsoWeek, Local, TimeZone};
+use clap::{Args, ValueEnum};
+use record_hours::{
+    Clock, Date, DecimalDuration, Event, Item, Log, MyDuration, SecondsDuration, Time,
+};
+
+use crate::{
+    config::Config, display_project, format, parse_duration, parse_since, project_group,
+    project_groups, project_key, read_log, show, DateRange, JsonDay, Now, ShowOptions, Since,
+    Total,
+};
+
+#[derive(Debug, Clone, Args)]
+pub struct ShowArgs {
+    /// show for specific project.
+    ///
+    /// May be repeated. Without --project, every project is shown.
+    ///
+    /// --project default (or "") shows only the default project
+    #[clap(short, long)]
+    project: Vec<String>,
+    /// display time in decimal format: e.g. 1 hour, 45 minutes = 1.75
+    #[clap(short, long)]
+    decimal: bool,
+    /// Display format.
+    ///
+    /// Variables that can be used for expansion:
+    /// %d => date in y-m-d
+    /// %Y => year
+    /// %M => month
+    /// %D => day
+    /// %w => weekday, e.g. Mon
+    /// %W => ISO week number
+    /// %t => decimal time that has been recorded
+    /// %h => hours that have been recorded
+    /// %m => minutes that have been recorded
+    /// %P => the project
+    /// %n => the notes of the day, separated by "; "
+    /// %% => a literal '%'
+    #[clap(short, long, conflicts_with_all = ["json", "markdown"])]
+    format: Option<String>,
+    /// only show days on or after this date (YYYY-MM-DD)
+    #[clap(long)]
+    from: Option<Date>,
+    /// only show days on or before this date (YYYY-MM-DD)
+    #[clap(long)]
+    to: Option<Date>,
+    /// only show days since 7d, 2w or 1m ago, or since the start of today, week or month
+    #[clap(long, conflicts_with = "from", value_parser = parse_since)]
+    since: Option<Since>,
+    /// only show today, same as --from <today> --to <today>
+    #[clap(long, conflicts_with_all = ["from", "since", "to"])]
+    today: bool,
+    /// only show this day (YYYY-MM-DD), same as --from <date> --to <date>
+    #[clap(long, conflicts_with_all = ["from", "since", "to", "today"])]
+    date: Option<Date>,
+    /// print the days as a json array instead of text
+    #[clap(long)]
+    json: bool,
+    /// print the days as a markdown table
+    #[clap(long, conflicts_with = "json")]
+    markdown: bool,
+    /// round every interval to the nearest multiple of this many minutes.
+    ///
+    /// Halves are rounded up. Day and grand totals are the sum of the rounded intervals
+    #[clap(long)]
+    round: Option<u32>,
+    /// round every day total to the nearest whole hour, the intervals are summed unrounded.
+    ///
+    /// Halves are rounded up. Rounded day totals are labelled as such
+    #[clap(long, conflicts_with = "round")]
+    round_day: bool,
+    /// move the start of every interval back and its end forward to the clock's multiples of
+    /// this many minutes, e.g. 09:07 - 10:52 becomes 09:00 - 11:00 with --snap 15.
+    ///
+    /// Unlike --round this changes the times themselves, durations follow from them. At most a
+    /// day (1440)
+    #[clap(long, value_parser = clap::value_parser!(u32).range(..=1440))]
+    snap: Option<u32>,
+    /// show consecutive intervals less than this many seconds apart as one, e.g. imported ones
+    /// that were split by short gaps. The log itself is not changed, unlike with prune
+    #[clap(long, value_name = "SECONDS")]
+    merge_gaps: Option<u32>,
+    /// also show the days without any entries as 0min, every day from --from to --to or between
+    /// the first and the last recorded day
+    #[clap(long)]
+    show_empty: bool,
+    /// with --show-empty, leave out saturdays and sundays that have no entries
+    #[clap(long, requires = "show_empty")]
+    skip_weekends: bool,
+    /// list the most recent day first
+    #[clap(long)]
+    reverse: bool,
+    /// show the breaks between the intervals of each day instead of the intervals
+    #[clap(long)]
+    invert: bool,
+    /// how long you want to work each day, e.g. 6h or 7h30m. Shows how far each day is off
+    #[clap(long, value_parser = parse_duration)]
+    target: Option<Duration>,
+    /// combine projects named like client/frontend into groups of this many levels, e.g. client.
+    ///
+    /// --project then selects groups instead of projects
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    depth: Option<u64>,
+    /// only show the last N days, after applying --from/--to
+    #[clap(long, value_name = "N")]
+    tail: Option<usize>,
+    /// show times and durations with second precision
+    #[clap(long)]
+    seconds: bool,
+    /// don't list open intervals, note them after the day total as "open since HH:MM" instead
+    #[clap(long)]
+    no_open: bool,
+    /// print the times of the text and markdown output in UTC instead of the local time.
+    ///
+    /// Days stay the local days, the log itself is not changed
+    #[clap(long)]
+    utc: bool,
+    /// hide intervals shorter than this many minutes, they don't count towards any total
+    #[clap(long, value_name = "MINUTES")]
+    min_duration: Option<u32>,
+    /// re-read the log and redraw every this many seconds, until interrupted with ctrl-c
+    #[clap(long, value_name = "SECONDS")]
+    watch: Option<u64>,
+    /// number of decimal places of decimal durations, implies --decimal. Defaults to 2
+    #[clap(long, value_name = "N")]
+    decimals: Option<usize>,
+    /// color the text output. auto colors only if stdout is a terminal and $NO_COLOR is not set
+    #[clap(long, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    fn enabled(&self) -> bool {
+        match self {
+            Color::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+const BOLD: &str = "1";
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+
+/// how times and durations are rendered
+#[derive(Debug, Clone, Copy)]
+struct Style<'a> {
+    /// decimal places if durations are shown in decimal
+    decimal: Option<usize>,
+    /// the configured duration format, used unless decimal or seconds are asked for
+    format: Option<&'a str>,
+    seconds: bool,
+    /// day totals were rounded to whole hours
+    round_day: bool,
+    /// daily target that every day is compared to
+    target: Option<Duration>,
+    /// use ansi colors
+    color: bool,
+    /// open intervals are noted with the day total instead of listed
+    no_open: bool,
+    /// times are converted from local time to UTC
+    utc: bool,
+}
+
+impl<'a> Style<'a> {
+    fn new(args: &ShowArgs, target: Option<Duration>, format: Option<&'a str>) -> Self {
+        Self {
+            decimal: args.decimals.or(args.decimal.then_some(2)),
+            format,
+            seconds: args.seconds,
+            round_day: args.round_day,
+            target,
+            // only the plain text output is colored
+            color: !args.json && !args.markdown && args.color.enabled(),
+            no_open: args.no_open,
+            utc: args.utc,
+        }
+    }
+
+    /// `text` in the ansi color `code`, if colors are enabled
+    fn paint(&self, code: &str, text: impl Display) -> String {
+        if self.color {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn duration(&self, duration: Duration) -> Box<dyn Display + 'a> {
+        if let Some(precision) = self.decimal {
+            Box::new(DecimalDuration::with_precision(duration, precision))
+        } else if self.seconds {
+            Box::new(SecondsDuration(duration))
+        } else if let Some(format) = self.format {
+            Box::new(format::DurationFormatter { duration, format })
+        } else {
+            Box::new(MyDuration(duration))
+        }
+    }
+
+    /// a day total, marked if it was rounded and compared to the target. With --no-open, the
+    /// start of the open interval `last` is noted as well
+    fn day(&self, date: &Date, duration: Duration, last: Option<Time>) -> String {
+        let mut day = self.paint(GREEN, self.duration(duration));
+        if self.round_day {
+            day += ", rounded";
+        }
+        if let Some(target) = self.target {
+            let diff = duration - target;
+            let sign = if diff < Duration::zero() { "-" } else { "+" };
+            day += &format!(
+                " / {}, {sign}{}",
+                self.duration(target),
+                self.duration(diff.abs())
+            );
+            if diff < Duration::zero() {
+                day += &self.paint(RED, " below target");
+            }
+        }
+        if let Some(start) = last.filter(|_| self.no_open) {
+            day += &self.paint(YELLOW, format!(", open since {}", self.time(date, start)));
+        }
+        day
+    }
+
+    /// `time` on `date`, in UTC with --utc
+    fn time(&self, date: &Date, time: Time) -> Box<dyn Display> {
+        let time = match Local
+            .from_local_datetime(&date.0.and_time(time.0))
+            .earliest()
+        {
+            Some(local) if self.utc => Time(local.naive_utc().time()),
+            _ => time,
+        };
+        if self.seconds {
+            Box::new(time.0.format("%H:%M:%S"))
+        } else {
+            Box::new(time)
+        }
+    }
+
+    fn total(&self, label: &str, total: &Total) {
+        let duration = self.duration(total.duration);
+        match total.open {
+            0 => println!("{label}: {duration}"),
+            open => println!("{label}: {duration} ({open} open interval(s) not counted)"),
+        }
+    }
+}
+
+/// run `show` once, or repeatedly if `--watch` was given
+pub fn run_file(args: &ShowArgs, path: &Path, config: &Config, clock: &Now) -> anyhow::Result<()> {
+    let Some(interval) = args.watch else {
+        let log = read_log(path)?;
+        return run(args, &log, config, clock);
+    };
+
+    loop {
+        // clear the screen and move the cursor to the top left
+        print!("\x1b[2J\x1b[H");
+        if let Err(e) = read_log(path).and_then(|log| run(args, &log, config, clock)) {
+            eprintln!("Error: {e}");
+        }
+        std::io::stdout().flush()?;
+        std::thread::sleep(std::time::Duration::from_secs(interval.max(1)));
+    }
+}
+
+pub fn run(args: &ShowArgs, log: &Log, config: &Config, clock: &Now) -> anyhow::Result<()> {
+    let now = clock.now();
+    let today = args.today.then(|| Date(now.date_naive()));
+    let single = args.date.or(today);
+    let range = match single {
+        Some(day) => DateRange::new(Some(day), Some(day))?,
+        None => {
+            let since = args.since.map(|since| since.start(now.date_naive()));
+            DateRange::new(args.from.or(since.transpose()?), args.to)?
+        }
+    };
+    let options = ShowOptions {
+        range,
+        round: args.round,
+        tail: args.tail,
+        min_duration: args.min_duration,
+        round_day: args.round_day,
+        snap: args.snap,
+        merge_gaps: args.merge_gaps,
+        show_empty: args.show_empty,
+        skip_weekends: args.skip_weekends,
+        events: true,
+        reverse: args.reverse,
+        invert: args.invert,
+        depth: args.depth.map(|depth| depth as usize),
+    };
+
+    if log.projects.is_empty() {
+        eprintln!("no data recorded");
+        return Ok(());
+    }
+    // without --project, every project is shown
+    let projects: Vec<String> = if args.project.is_empty() {
+        project_groups(log, options.depth)
+    } else {
+        args.project
+            .iter()
+            .map(|p| project_key(Some(p.clone())))
+            .collect()
+    };
+
+    if let Some(day) = single {
+        let active = log.projects.iter().any(|(name, info)| {
+            let group = options
+                .depth
+                .map_or(name.as_str(), |d| project_group(name, d));
+            projects.iter().any(|project| project == group)
+                && (info.entries.contains_key(&day)
+                    || info.events.iter().any(|event| event.date == day))
+        });
+        if !active {
+            match today {
+                Some(_) => println!("no activity today"),
+                None => println!("no activity on {day}"),
+            }
+            return Ok(());
+        }
+    }
+
+    let style = Style::new(args, config.target(args.target)?, config.duration_format()?);
+    let total = if args.json {
+        json(log, &projects, &options)?
+    } else if args.markdown {
+        markdown(log, &projects, &options, &style)?
+    } else if let Some(format) = args.format.as_ref().or(config.default_format.as_ref()) {
+        template(log, &projects, &options, format)?
+    } else {
+        text(log, &projects, &options, args, &style, now)?
+    };
+
+    if let Some(minutes) = args.min_duration.filter(|_| total.filtered != 0) {
+        eprintln!(
+            "{} interval(s) shorter than {minutes}min were hidden",
+            total.filtered
+        );
+    }
+    if total.days == 0 {
+        let projects = projects
+            .iter()
+            .map(|project| display_project(project))
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(anyhow!(
+            "no entries for project {projects} in the given range"
+        ));
+    }
+    Ok(())
+}
+
+fn json(log: &Log, projects: &[String], options: &ShowOptions) -> anyhow::Result<Total> {
+    let mut total = Total::default();
+    let mut days = vec![];
+    for project in projects {
+        let events = events(log, project, options);
+        let project_total = show(log, project, options, |&date, intervals, last, duration| {
+            days.push(JsonDay {
+                project: project.clone(),
+                date,
+                duration_seconds: duration.num_seconds(),
+                intervals,
+                open: last.is_some(),
+                events: events
+                    .iter()
+                    .filter(|event| event.date == date)
+                    .map(|&event| event.clone())
+                    .collect(),
+            });
+            Ok(())
+        })?;
+        total.add(&project_total);
+    }
+    serde_json::to_writer(std::io::stdout().lock(), &days)?;
+    println!();
+    Ok(total)
+}
+
+/// the events of `project`, or of all of its members with --depth, by date and time
+fn events<'a>(log: &'a Log, project: &str, options: &ShowOptions) -> Vec<&'a Event> {
+    let mut events: Vec<_> = log
+        .projects
+        .iter()
+        .filter(|(name, _)| match options.depth {
+            Some(depth) => project_group(name, depth) == project,
+            None => *name == project,
+        })
+        .flat_map(|(_, info)| &info.events)
+        .collect();
+    events.sort_by_key(|event| (event.date, event.time));
+    events
+}
+
+/// the heading of `project`, marked if it is not billable
+fn heading(log: &Log, project: &str) -> String {
+    let name = display_project(project);
+    match log.projects.get(project).and_then(|info| info.billable) {
+        Some(false) => format!("{name} (non-billable)"),
+        _ => name.to_string(),
+    }
+}
+
+fn markdown(
+    log: &Log,
+    projects: &[String],
+    options: &ShowOptions,
+    style: &Style,
+) -> anyhow::Result<Total> {
+    let headings = projects.len() > 1;
+    let mut total = Total::default();
+    for (i, project) in projects.iter().enumerate() {
+        if headings {
+            let separator = if i == 0 { "" } else { "\n" };
+            println!("{separator}### {}\n", heading(log, project));
+        }
+        println!("| Date | Intervals | Duration |");
+        println!("| --- | --- | --- |");
+        let events = events(log, project, options);
+        let project_total = show(log, project, options, |date, times, last, duration| {
+            // events are listed between the intervals by their time, like in the text output
+            let mut lines: Vec<_> = times
+                .iter()
+                .map(|Item { start, end, .. }| {
+                    let line = format!("{}–{}", style.time(date, *start), style.time(date, *end));
+                    (*start, line)
+                })
+                .chain(
+                    last.filter(|_| !style.no_open)
+                        .map(|start| (start, format!("{}–", style.time(date, start)))),
+                )
+                .chain(
+                    events
+                        .iter()
+                        .filter(|event| event.date == *date)
+                        .map(|event| {
+                            let label = event.label.replace('|', "\\|");
+                            (
+                                event.time,
+                                format!("* {} {label}", style.time(date, event.time)),
+                            )
+                        }),
+                )
+                .collect();
+            // stable, so an event at the start of an interval comes after it
+            lines.sort_by_key(|(time, _)| *time);
+            let intervals = lines
+                .into_iter()
+                .map(|(_, line)| line)
+                .collect::<Vec<_>>()
+                .join("<br>");
+            let duration = style.day(date, duration, last);
+            println!("| {date} | {intervals} | {duration} |");
+            Ok(())
+        })?;
+        total.add(&project_total);
+    }
+    Ok(total)
+}
+
+fn template(
+    log: &Log,
+    projects: &[String],
+    options: &ShowOptions,
+    format: &str,
+) -> anyhow::Result<Total> {
+    let mut total = Total::default();
+    for project in projects {
+        let project_total = show(log, project, options, |&date, times, _, duration| {
+            let note = times
+                .iter()
+                .filter_map(|item| item.note.as_deref())
+                .collect::<Vec<_>>()
+                .join("; ");
+            let fmt = format::Formatter {
+                date,
+                duration,
+                format,
+                project,
+                note: &note,
+            };
+            // a `Display` error would make `println!` panic, so render it first
+            let mut line = String::new();
+            std::fmt::write(&mut line, format_args!("{fmt}"))
+                .map_err(|_| anyhow!("invalid format string {format:?}"))?;
+            println!("{line}");
+            Ok(())
+        })?;
+        total.add(&project_total);
+    }
+    Ok(total)
+}
+
+fn text(
+    log: &Log,
+    projects: &[String],
+    options: &ShowOptions,
+    args: &ShowArgs,
+    style: &Style,
+    now: DateTime<FixedOffset>,
+) -> anyhow::Result<Total> {
+    let headings = projects.len() > 1;
+    let mut grand_total = Total::default();
+    for (i, project) in projects.iter().enumerate() {
+        if headings {
+            let separator = if i == 0 { "" } else { "\n" };
+            println!("{separator}# {}", heading(log, project));
+        }
+        // the ISO week of the days printed so far and its total, a week total is printed when
+        // the next week starts, so a single week is shown without one
+        let mut week: Option<(IsoWeek, Duration)> = None;
+        let mut weeks = 0;
+        let events = events(log, project, options);
+        let total = show(log, project, options, |date, times, last, duration| {
+            let mut f = std::io::stdout().lock();
+            match &mut week {
+                Some((current, sum)) if *current == date.0.iso_week() => *sum += duration,
+                previous => {
+                    if let Some((_, sum)) = previous {
+                        writeln!(f, "  Week total: {}", style.duration(*sum))?;
+                        weeks += 1;
+                    }
+                    *previous = Some((date.0.iso_week(), duration));
+                }
+            }
+            let duration = style.day(date, duration, last);
+            writeln!(f, "{} ({}):", style.paint(BOLD, date), duration)?;
+            let mut marks = events.iter().filter(|event| event.date == *date).peekable();
+            let mark = |f: &mut StdoutLock, event: &Event| {
+                writeln!(f, "  * {} {}", style.time(date, event.time), event.label)
+            };
+            for item in times {
+                let Item { start, end, .. } = item;
+                while let Some(event) = marks.next_if(|event| event.time < start) {
+                    mark(&mut f, event)?;
+                }
+                let duration = style.duration(options.item_duration(&item));
+                write!(
+                    f,
+                    "  - {} - {} ({duration})",
+                    style.time(date, start),
+                    style.time(date, end)
+                )?;
+                match item.note {
+                    Some(note) => writeln!(f, ": {note}")?,
+                    None => writeln!(f)?,
+                }
+            }
+            for event in marks {
+                mark(&mut f, event)?;
+            }
+            if let Some(start) = last.filter(|_| !style.no_open) {
+                if date.0 == now.date_naive() {
+                    let elapsed = style.duration(now.time() - start.0);
+                    let ongoing = format!("(ongoing, {elapsed})");
+                    writeln!(
+                        f,
+                        "  - {} - {}",
+                        style.time(date, start),
+                        style.paint(YELLOW, ongoing)
+                    )?;
+                } else {
+                    log::warn!("interval starting {date} {start} was never closed");
+                    writeln!(f, "  - {} - ", style.paint(RED, style.time(date, start)))?;
+                }
+            }
+            Ok(())
+        })?;
+        if let Some((_, sum)) = week.filter(|_| weeks > 0) {
+            println!("  Week total: {}", style.duration(sum));
+        }
+
+        style.total(if args.invert { "Total breaks" } else { "Total" }, &total);
+        grand_total.add(&total);
+    }
+    if headings {
+        println!();
+        let label = if args.invert {
+            "Grand total breaks"
+        } else {
+            "Grand total"
+        };
+        style.total(label, &grand_total);
+    }
+    Ok(grand_total)
+}